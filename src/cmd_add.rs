@@ -1,11 +1,14 @@
 use crate::cmd_list;
-use crate::model::{Section, Task};
+use crate::model::{FileFormat, Section, Task, TaskState};
 use crate::services::{ContentGetter, ContentSetter, StringOutputer, TaskFormatter, UserCmdRunner};
 use crate::tasks::{
-    filter_tasks_in_section, get_all_tasks, search_section, task_to_markdown, text_add_line_in_str,
-    text_get_comment, text_is_focused, text_remove_focus, text_replace_line_in_str,
+    filter_tasks_in_section_only, get_all_tasks, get_new_task_id, search_section,
+    section_is_within, task_to_line, text_add_line_in_str, text_get_comment, text_get_contexts,
+    text_get_created_date, text_get_due, text_get_priority, text_get_projects, text_get_tags,
+    text_is_file_marker, text_is_focused, text_remove_focus, text_set_created,
 };
 
+use chrono::NaiveDate;
 use std::rc::Rc;
 
 pub enum AddPosition {
@@ -13,6 +16,7 @@ pub enum AddPosition {
     Prepend,
 }
 
+#[allow(clippy::too_many_arguments, clippy::question_mark)]
 pub fn cmd(
     outputer: &mut dyn StringOutputer,
     content_getter: &dyn ContentGetter,
@@ -21,19 +25,66 @@ pub fn cmd(
     task_formatter: &TaskFormatter,
     task_parts: Vec<String>,
     section_name: Option<String>,
+    default_section: Option<String>,
     pos: AddPosition,
+    created_stamp: Option<String>,
+    id_seed: u64,
 ) -> Result<(), String> {
     let task_name = task_parts.join(" ");
 
-    let (tasks, _use_sections, sections, focused_section) = get_all_tasks(content_getter)?;
+    let (tasks, _use_sections, sections, focused_sections) = get_all_tasks(content_getter)?;
+    // section of the task when none is given: the focused section (the first
+    // one when several sections are focused), or else the default section
+    let implicit_section: Option<Section> = match (focused_sections.first(), &default_section) {
+        (Some(section), _) => Some(section.as_ref().clone()),
+        (None, Some(name)) => match search_section(name, &sections) {
+            Some(section) => Some(section),
+            None => return Err(format!("Section not found: {}", name)),
+        },
+        (None, None) => None,
+    };
+
+    let format = content_getter.get_format();
 
     let (name_without_comment, comment) = text_get_comment(task_name.as_str());
-    let is_task_focused = text_is_focused(name_without_comment.as_str());
+    let is_task_focused = text_is_focused(name_without_comment.as_str(), format);
     let plain_name = if is_task_focused {
-        text_remove_focus(name_without_comment.as_str())
+        text_remove_focus(name_without_comment.as_str(), format)
     } else {
         name_without_comment
     };
+    let target_section = match &section_name {
+        Some(name) => search_section(name, &sections),
+        None => implicit_section.clone(),
+    };
+    // a todo.txt project needs no heading: its first task starts it
+    let new_project = match (&section_name, &target_section, format) {
+        (Some(name), None, FileFormat::TodoTxt) => Some(name.trim_start_matches('+').to_string()),
+        _ => None,
+    };
+
+    let plain_name = match (&created_stamp, format) {
+        // todo.txt has its own place for the creation date
+        (Some(_), FileFormat::TodoTxt) | (None, _) => plain_name,
+        (Some(stamp), _) => text_set_created(&plain_name, Some(stamp)),
+    };
+    let plain_name = match (&target_section, format) {
+        // a todo.txt task belongs to the section of its first +project
+        (Some(section), FileFormat::TodoTxt)
+            if text_get_projects(&plain_name).first() != Some(&section.name) =>
+        {
+            format!("{} +{}", plain_name, section.name)
+        }
+        (None, FileFormat::TodoTxt) => match &new_project {
+            Some(project) if text_get_projects(&plain_name).first() != Some(project) => {
+                format!("{} +{}", plain_name, project)
+            }
+            _ => plain_name,
+        },
+        _ => plain_name,
+    };
+    let id = get_new_task_id(&tasks, id_seed);
+    let plain_name = format!("{} ^{}", plain_name, id);
 
     let mut new_task = Task {
         name: task_name.clone(),
@@ -41,13 +92,41 @@ pub fn cmd(
         comment,
         line: String::from(""),
         line_num: 0,
-        is_checked: false,
+        state: TaskState::Open,
         is_focused: is_task_focused,
         num: 0,
         section: None,
+        due: text_get_due(&plain_name),
+        tags: text_get_tags(&plain_name),
+        contexts: text_get_contexts(&plain_name),
+        priority: text_get_priority(&plain_name),
+        created: text_get_created_date(&plain_name),
+        id: Some(id),
+        format,
+        ..Default::default()
     };
 
-    new_task.line = task_to_markdown(&new_task);
+    if format == FileFormat::TodoTxt {
+        new_task.created = created_stamp
+            .as_deref()
+            .and_then(|stamp| NaiveDate::parse_from_str(stamp.get(..10)?, "%Y-%m-%d").ok());
+    }
+
+    if format == FileFormat::Org {
+        // the new task is written like the other tasks where it is added:
+        // a TODO headline, or a checkbox item
+        let target_tasks = match &target_section {
+            Some(section) => filter_tasks_in_section_only(&tasks, section),
+            None => tasks.clone(),
+        };
+
+        new_task.org_level = match target_tasks.iter().find(|t| t.depth == 0) {
+            Some(task) => task.org_level,
+            None => Some(target_section.map_or(1, |s| s.level + 1)),
+        };
+    }
+
+    new_task.line = task_to_line(&new_task);
     let mut added: bool = false;
     let mut display_all: bool = false;
 
@@ -55,6 +134,7 @@ pub fn cmd(
         None => (),
         Some(name) => match search_section(&name, &sections) {
             // Section has been specified
+            None if new_project.is_some() => display_all = !focused_sections.is_empty(),
             None => return Err(format!("Section not found: {}", name)), // but does not exist;
             Some(section) => {
                 match add_to_section(
@@ -73,24 +153,24 @@ pub fn cmd(
                         new_task.num = task_num;
 
                         // display all tasks if task added to section not focused
-                        display_all = match &focused_section {
-                            None => false,
-                            Some(f) => f.num != section.num,
-                        };
+                        display_all = !focused_sections.is_empty()
+                            && !focused_sections
+                                .iter()
+                                .any(|f| section_is_within(&section, f));
                     }
                 };
             }
         },
     };
 
-    if !added {
-        match focused_section {
+    if !added && new_project.is_none() {
+        match implicit_section {
             None => (),
             Some(s) => {
                 match add_to_section(
                     &tasks,
                     &sections,
-                    s.as_ref(),
+                    &s,
                     &new_task.line,
                     content_getter,
                     content_setter,
@@ -108,20 +188,24 @@ pub fn cmd(
     }
 
     if !added {
+        let content = content_getter.get_contents()?;
         let (line_num, task_num) = if tasks.is_empty() {
-            (1, 1)
+            // the first task of an aggregated list goes to its first file
+            match content.lines().next() {
+                Some(line) if text_is_file_marker(line) => (2, 1),
+                _ => (1, 1),
+            }
         } else {
             match pos {
                 AddPosition::Prepend => (tasks[0].line_num, 1),
                 AddPosition::Append => (
-                    tasks[tasks.len() - 1].line_num + 1,
+                    tasks.iter().map(|t| t.line_num_end).max().unwrap() + 1,
                     tasks[tasks.len() - 1].num + 1,
                 ),
             }
         };
 
-        let new_content =
-            text_add_line_in_str(&content_getter.get_contents()?, line_num, &new_task.line);
+        let new_content = text_add_line_in_str(&content, line_num, &new_task.line);
         content_setter.set_contents(new_content)?;
 
         new_task.line_num = line_num;
@@ -136,10 +220,18 @@ pub fn cmd(
         },
         &new_task,
     ) {
-        return Err(e)
+        return Err(e);
     }
 
-    cmd_list::cmd(outputer, content_getter, task_formatter, display_all) // FIXME: all or not depending of whether the task is in displayed section
+    cmd_list::cmd(
+        outputer,
+        content_getter,
+        task_formatter,
+        &cmd_list::ListOptions {
+            all: display_all, // FIXME: all or not depending of whether the task is in displayed section
+            ..Default::default()
+        },
+    )
 }
 
 fn call_user_cmd_runner(
@@ -154,6 +246,7 @@ fn call_user_cmd_runner(
     }
 }
 
+#[allow(clippy::question_mark)]
 fn add_to_section(
     tasks: &[Task],
     sections: &[Rc<Section>],
@@ -164,7 +257,7 @@ fn add_to_section(
     pos: &AddPosition,
 ) -> Result<(usize, usize), String> {
     // Add task to section
-    let section_tasks = filter_tasks_in_section(tasks, section);
+    let section_tasks = filter_tasks_in_section_only(tasks, section);
 
     let content = match content_getter.get_contents() {
         Err(e) => return Err(e),
@@ -173,7 +266,6 @@ fn add_to_section(
 
     let line_num: usize;
     let task_num: usize;
-    let new_lines: String;
     let new_content: String;
 
     if section_tasks.is_empty() {
@@ -186,7 +278,7 @@ fn add_to_section(
 
             while cur_section_num >= 1 {
                 let tasks_in_cur_section =
-                    filter_tasks_in_section(tasks, sections[cur_section_num].as_ref());
+                    filter_tasks_in_section_only(tasks, sections[cur_section_num].as_ref());
                 if !tasks_in_cur_section.is_empty() {
                     found = Some(match pos {
                         AddPosition::Append => {
@@ -203,30 +295,20 @@ fn add_to_section(
             found.unwrap_or(1)
         };
 
-        new_lines = format!("\n{}", new_line);
         line_num = section.line_num_end + 1;
-        new_content = text_add_line_in_str(&content, line_num, &new_lines);
+        new_content = text_add_line_in_str(&content, line_num, &format!("\n{}", new_line));
     } else {
-        let (new_lines_tmp, line_num_tmp, task_num_tmp) = match pos {
-            AddPosition::Prepend => (
-                format!("{}\n{}", new_line, section_tasks[0].line),
-                section_tasks[0].line_num,
-                section_tasks[0].num,
-            ),
+        let (line_num_tmp, task_num_tmp) = match pos {
+            AddPosition::Prepend => (section_tasks[0].line_num, section_tasks[0].num),
             AddPosition::Append => (
-                format!(
-                    "{}\n{}",
-                    section_tasks[section_tasks.len() - 1].line,
-                    new_line,
-                ),
-                section_tasks[section_tasks.len() - 1].line_num,
+                // after the body of the last task of the section
+                section_tasks.iter().map(|t| t.line_num_end).max().unwrap() + 1,
                 section_tasks[section_tasks.len() - 1].num + 1,
             ),
         };
-        new_lines = new_lines_tmp;
         line_num = line_num_tmp;
         task_num = task_num_tmp;
-        new_content = text_replace_line_in_str(&content, line_num, &new_lines);
+        new_content = text_add_line_in_str(&content, line_num, new_line);
     }
 
     match content_setter.set_contents(new_content) {
@@ -258,12 +340,15 @@ mod tests {
             &task_formatter,
             vec!["**Some focused task** // with comments; see https://example.com".to_string()],
             None,
+            None,
             AddPosition::Prepend,
+            None,
+            0,
         ) {
             Ok(()) => assert_eq!(
                 content_setter.content,
                 Some(String::from(
-                    "- [ ] **Some focused task** // with comments; see https://example.com\n"
+                    "- [ ] **Some focused task** ^7iyc // with comments; see https://example.com\n"
                 ))
             ),
             Err(e) => panic!("{}", e),
@@ -286,11 +371,14 @@ mod tests {
             &task_formatter,
             vec!["Some task".to_string()],
             None,
+            None,
             AddPosition::Prepend,
+            None,
+            0,
         ) {
             Ok(()) => assert_eq!(
                 content_setter.content,
-                Some(String::from("- [ ] Some task\n- [ ] Existing task\n"))
+                Some(String::from("- [ ] Some task ^7iyc\n- [ ] Existing task"))
             ),
             Err(e) => panic!("{}", e),
         }
@@ -312,17 +400,21 @@ mod tests {
             &task_formatter,
             vec!["Some task".to_string()],
             None,
+            None,
             AddPosition::Append,
+            None,
+            0,
         ) {
             Ok(()) => assert_eq!(
                 content_setter.content,
-                Some(String::from("- [ ] Existing task\n- [ ] Some task\n"))
+                Some(String::from("- [ ] Existing task\n- [ ] Some task ^7iyc"))
             ),
             Err(e) => panic!("{}", e),
         }
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn test_cmd_add_top_section() {
         let mut string_outputer = StringOutputerMock::new();
         let content_getter = ContentGetterMock::new(Ok(vec![
@@ -343,12 +435,15 @@ mod tests {
             &task_formatter,
             vec!["Some task".to_string()],
             None,
+            None,
             AddPosition::Prepend,
+            None,
+            0,
         ) {
             Ok(()) => assert_eq!(
                 content_setter.content,
                 Some(String::from(
-                    "# Section\n\n- [ ] Some task\n- [ ] Existing task\n"
+                    "# Section\n\n- [ ] Some task ^7iyc\n- [ ] Existing task"
                 ))
             ),
             Err(e) => panic!("{}", e),
@@ -356,6 +451,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn test_cmd_add_bottom_section() {
         let mut string_outputer = StringOutputerMock::new();
         let content_getter = ContentGetterMock::new(Ok(vec![
@@ -376,15 +472,313 @@ mod tests {
             &task_formatter,
             vec!["Some task".to_string()],
             None,
+            None,
+            AddPosition::Append,
+            None,
+            0,
+        ) {
+            Ok(()) => assert_eq!(
+                content_setter.content,
+                Some(String::from(
+                    "# Section\n\n- [ ] Existing task\n- [ ] Some task ^7iyc"
+                ))
+            ),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn test_cmd_add_stamped() {
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+
+        match cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new(Ok("- [ ] Existing task".to_string())),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            vec!["**Some task** // with comments".to_string()],
+            None,
+            None,
+            AddPosition::Prepend,
+            Some(String::from("2020-06-15")),
+            0,
+        ) {
+            Ok(()) => assert_eq!(
+                content_setter.content,
+                Some(String::from(
+                    "- [ ] **Some task** created:2020-06-15 ^7iyc // with comments\n- [ ] Existing task"
+                ))
+            ),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn test_cmd_add_org() {
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+
+        match cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new_org(Ok(String::from(
+                "* Job\n** TODO Existing task\n* Perso\n- [ ] Fix the roof",
+            ))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            vec!["*Some task*".to_string()],
+            Some(String::from("Job")),
+            None,
+            AddPosition::Append,
+            None,
+            0,
+        ) {
+            Ok(()) => assert_eq!(
+                content_setter.content,
+                Some(String::from(
+                    "* Job\n** TODO Existing task\n** TODO *Some task* ^7iyc\n* Perso\n- [ ] Fix the roof"
+                ))
+            ),
+            Err(e) => panic!("{}", e),
+        }
+
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+
+        match cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new_org(Ok(String::from(
+                "* Job\n** TODO Existing task\n* Perso\n- [ ] Fix the roof",
+            ))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            vec!["Some task".to_string()],
+            Some(String::from("Perso")),
+            None,
+            AddPosition::Prepend,
+            None,
+            0,
+        ) {
+            Ok(()) => assert_eq!(
+                content_setter.content,
+                Some(String::from(
+                    "* Job\n** TODO Existing task\n* Perso\n- [ ] Some task ^7iyc\n- [ ] Fix the roof"
+                ))
+            ),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn test_cmd_add_todotxt() {
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+
+        match cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new_todotxt(Ok(String::from(
+                "Fix the roof +home\n(A) Send the invoice +job\nBuy milk +home",
+            ))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            vec!["(B) Paint the door".to_string()],
+            Some(String::from("home")),
+            None,
+            AddPosition::Append,
+            Some(String::from("2020-06-15T09:30")),
+            0,
+        ) {
+            Ok(()) => assert_eq!(
+                content_setter.content,
+                Some(String::from(
+                    "Fix the roof +home\n(A) Send the invoice +job\nBuy milk +home\n(B) 2020-06-15 Paint the door +home ^7iyc"
+                ))
+            ),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn test_cmd_add_todotxt_new_project() {
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+
+        match cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new_todotxt(Ok(String::from(
+                "# focus: +job\nFix the roof +home\n(A) Send the invoice +job",
+            ))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            vec!["Plant a tree".to_string()],
+            Some(String::from("garden")),
+            None,
             AddPosition::Append,
+            None,
+            0,
         ) {
             Ok(()) => assert_eq!(
                 content_setter.content,
                 Some(String::from(
-                    "# Section\n\n- [ ] Existing task\n- [ ] Some task\n"
+                    "# focus: +job\nFix the roof +home\n(A) Send the invoice +job\nPlant a tree +garden ^7iyc"
                 ))
             ),
             Err(e) => panic!("{}", e),
         }
     }
+
+    #[test]
+    fn test_cmd_add_default_section() {
+        let content =
+            "---\nsection: Inbox\n---\n# Job\n- [ ] Send the invoice\n# Inbox\n- [ ] Buy milk\n";
+
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        match cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new(Ok(String::from(content))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            vec!["Call mom".to_string()],
+            None,
+            Some(String::from("Inbox")),
+            AddPosition::Append,
+            None,
+            0,
+        ) {
+            Ok(()) => assert_eq!(
+                content_setter.content,
+                Some(String::from(
+                    "---\nsection: Inbox\n---\n# Job\n- [ ] Send the invoice\n# Inbox\n- [ ] Buy milk\n- [ ] Call mom ^7iyc\n"
+                ))
+            ),
+            Err(e) => panic!("{}", e),
+        }
+
+        // a focused section wins over the default section
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new(Ok(content.replace("# Job", "# **Job**"))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            vec!["Call mom".to_string()],
+            None,
+            Some(String::from("Inbox")),
+            AddPosition::Append,
+            None,
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            content_setter.content,
+            Some(String::from(
+                "---\nsection: Inbox\n---\n# **Job**\n- [ ] Send the invoice\n- [ ] Call mom ^7iyc\n# Inbox\n- [ ] Buy milk\n"
+            ))
+        );
+
+        assert_eq!(
+            cmd(
+                &mut StringOutputerMock::new(),
+                &ContentGetterMock::new(Ok(String::from(content))),
+                &mut ContentSetterMock::new(Ok(())),
+                &UserCmdRunnerMock::new(),
+                &TaskFormatter::new(false),
+                vec!["Call mom".to_string()],
+                None,
+                Some(String::from("Someday")),
+                AddPosition::Append,
+                None,
+                0,
+            ),
+            Err(String::from("Section not found: Someday"))
+        );
+    }
+
+    #[test]
+    fn test_cmd_add_aggregated() {
+        use crate::cmd_check;
+        use crate::services::ContentHandlerReal;
+        use crate::settings::Settings;
+        use crate::test_helpers::test::now_getter_fixed;
+        use std::fs;
+
+        // the first task of an empty list goes after the first marker
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new(Ok(String::from(
+                "<!-- tax file: home.md -->\n<!-- tax file: work.md -->\n",
+            ))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            vec!["Fix the roof".to_string()],
+            None,
+            None,
+            AddPosition::Append,
+            None,
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            content_setter.content,
+            Some(String::from(
+                "<!-- tax file: home.md -->\n- [ ] Fix the roof ^7iyc\n<!-- tax file: work.md -->\n"
+            ))
+        );
+
+        // a command reading the list again after writing it, like `tax add`,
+        // and a second command of the same run
+        let dir = std::env::temp_dir().join(format!("tax-test-add-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("home.md"), "").unwrap();
+        fs::write(dir.join("work.md"), "").unwrap();
+
+        let read_hash = Rc::default();
+        let content_handler_ref = &ContentHandlerReal {
+            path: String::from(dir.to_str().unwrap()),
+            read_hash: Rc::clone(&read_hash),
+        };
+        let content_handler_mutref = &mut ContentHandlerReal {
+            path: String::from(dir.to_str().unwrap()),
+            read_hash,
+        };
+
+        cmd(
+            &mut StringOutputerMock::new(),
+            content_handler_ref,
+            content_handler_mutref,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            vec!["Fix the roof".to_string()],
+            None,
+            None,
+            AddPosition::Append,
+            None,
+            0,
+        )
+        .unwrap();
+        cmd_check::cmd(
+            &mut StringOutputerMock::new(),
+            content_handler_ref,
+            content_handler_mutref,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            &Settings::default(),
+            now_getter_fixed(),
+            0,
+            1,
+            TaskState::Done,
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("home.md")).unwrap(),
+            "- [x] Fix the roof ^7iyc\n"
+        );
+        assert_eq!(fs::read_to_string(dir.join("work.md")).unwrap(), "");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
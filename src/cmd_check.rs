@@ -1,63 +1,146 @@
+use crate::model::{FileFormat, Task, TaskState};
 use crate::services::{ContentGetter, ContentSetter, StringOutputer, TaskFormatter, UserCmdRunner};
-use crate::tasks::{get_all_tasks, task_to_markdown, text_replace_line_in_str};
+use crate::settings::{RecurMode, Settings};
+use crate::tasks::{
+    get_all_tasks, get_new_task_id, get_next_due, get_task_ancestors, get_task_blockers,
+    get_task_descendants, get_task_line_nums, state_is_closed, task_to_line, text_add_line_in_str,
+    text_format_stamp, text_get_done_date, text_replace_line_in_str, text_set_done, text_set_due,
+    text_set_id,
+};
+use chrono::{NaiveDate, NaiveDateTime};
 
+#[allow(clippy::too_many_arguments)]
 pub fn cmd(
     outputer: &mut dyn StringOutputer,
     content_getter: &dyn ContentGetter,
     content_setter: &mut dyn ContentSetter,
     user_cmd_runner: &dyn UserCmdRunner,
     task_formatter: &TaskFormatter,
+    settings: &Settings,
+    now: NaiveDateTime,
+    id_seed: u64,
     rank_one_based: usize,
-    checked: bool,
+    state: TaskState,
 ) -> Result<(), String> {
     let (tasks, use_sections, _, _) = get_all_tasks(content_getter)?;
-    if rank_one_based > tasks.len() {
+    if rank_one_based == 0 || rank_one_based > tasks.len() {
         return Err(format!("Non existent task {}", rank_one_based));
     }
 
     let task = &tasks[rank_one_based - 1];
+    let (cmd_name, operation, action, description) = get_state_change(state);
 
-    if checked && task.is_checked {
+    if task.state == state {
         outputer.info(&format!(
-            "Already checked: {}",
-            task_formatter.display_numbered_task(task, use_sections, true)
-        ));
-        return Ok(());
-    } else if !checked && !task.is_checked {
-        outputer.info(&format!(
-            "Already unckecked: {}",
+            "Already {}: {}",
+            action.to_lowercase(),
             task_formatter.display_numbered_task(task, use_sections, true)
         ));
         return Ok(());
     }
 
-    let mut updated_task = task.clone();
-    updated_task.is_checked = checked;
-    updated_task.line = task_to_markdown(&updated_task);
+    let done_stamp = text_format_stamp(settings.stamp_done, now);
+    let updated_task = update_task_state(task, state, done_stamp.as_deref(), now.date());
 
-    let action = if checked { "Checked" } else { "Unchecked" };
     outputer.info(&format!(
         "{}: {}",
         action,
         task_formatter.display_numbered_task(&updated_task, use_sections, true)
     ));
 
-    let replaced_content = text_replace_line_in_str(
+    let mut replaced_content = text_replace_line_in_str(
         &content_getter.get_contents()?,
         updated_task.line_num,
         &updated_task.line,
     );
 
+    let related_tasks = get_related_tasks_to_update(&tasks, task, state);
+    for (related_task, related_state) in related_tasks.iter().cloned() {
+        let updated_related_task = update_task_state(
+            &related_task,
+            related_state,
+            done_stamp.as_deref(),
+            now.date(),
+        );
+        outputer.info(&format!(
+            "{}: {}",
+            get_state_change(related_state).2,
+            task_formatter.display_numbered_task(&updated_related_task, use_sections, true)
+        ));
+
+        replaced_content = text_replace_line_in_str(
+            &replaced_content,
+            updated_related_task.line_num,
+            &updated_related_task.line,
+        );
+    }
+
+    if state_is_closed(state) {
+        // tasks whose last open blocker has just been closed
+        let mut closed_nums: Vec<usize> = vec![task.num];
+        for (related_task, related_state) in &related_tasks {
+            if state_is_closed(*related_state) {
+                closed_nums.push(related_task.num);
+            }
+        }
+
+        for unblocked_task in tasks.iter().filter(|t| {
+            t.is_blocked
+                && !closed_nums.contains(&t.num)
+                && get_task_blockers(&tasks, t)
+                    .iter()
+                    .all(|b| state_is_closed(b.state) || closed_nums.contains(&b.num))
+        }) {
+            let mut unblocked_task = unblocked_task.clone();
+            unblocked_task.is_blocked = false;
+            outputer.info(&format!(
+                "Unblocked: {}",
+                task_formatter.display_numbered_task(&unblocked_task, use_sections, true)
+            ));
+        }
+
+        // a cancelled recurring task stops recurring
+        if let (Some(recurrence), TaskState::Done) = (&task.recurrence, state) {
+            let mut next_task = update_task_state(task, TaskState::Open, None, now.date());
+            let next_due = get_next_due(recurrence, task.due, now.date());
+            next_task.name = text_set_due(&next_task.name, next_due);
+            next_task.plain_name = text_set_due(&next_task.plain_name, next_due);
+            next_task.due = Some(next_due);
+            // an inserted copy is a task of its own, with its own id
+            if let (RecurMode::Insert, Some(_)) = (&settings.recur_mode, &task.id) {
+                let id = get_new_task_id(&tasks, id_seed);
+                next_task.name = text_set_id(&next_task.name, &id);
+                next_task.plain_name = text_set_id(&next_task.plain_name, &id);
+                next_task.id = Some(id);
+            }
+            next_task.line = task_to_line(&next_task);
+
+            replaced_content = match settings.recur_mode {
+                RecurMode::Insert => {
+                    // after the subtree of the closed task
+                    let line_num = get_task_line_nums(&tasks, task).into_iter().max().unwrap();
+                    next_task.line_num = line_num + 1;
+                    next_task.num = task.num + 1 + get_task_descendants(&tasks, task).len();
+                    text_add_line_in_str(&replaced_content, next_task.line_num, &next_task.line)
+                }
+                RecurMode::Replace => {
+                    text_replace_line_in_str(&replaced_content, next_task.line_num, &next_task.line)
+                }
+            };
+
+            outputer.info(&format!(
+                "Next: {}",
+                task_formatter.display_numbered_task(&next_task, use_sections, true)
+            ));
+        }
+    }
+
     let result = content_setter.set_contents(replaced_content);
 
     match user_cmd_runner.build(
-        "check",
-        if checked { "CHECK" } else { "UNCHECK" },
-        &format!(
-            "Marked \"{}\" as {}",
-            updated_task.name,
-            if checked { "done" } else { "not done" }
-        ),
+        cmd_name,
+        operation,
+        &format!("Marked \"{}\" as {}", updated_task.name, description),
     ) {
         Ok(Some(mut cmd)) => {
             user_cmd_runner.run(user_cmd_runner.env_single_task(&updated_task, &mut cmd))?;
@@ -68,3 +151,548 @@ pub fn cmd(
 
     result
 }
+
+// (command, operation, action, description) of a change to the given state
+fn get_state_change(state: TaskState) -> (&'static str, &'static str, &'static str, &'static str) {
+    match state {
+        TaskState::Done => ("check", "CHECK", "Checked", "done"),
+        TaskState::Open => ("check", "UNCHECK", "Unchecked", "not done"),
+        TaskState::InProgress => ("start", "START", "Started", "in progress"),
+        TaskState::Cancelled => ("cancel", "CANCEL", "Cancelled", "cancelled"),
+        TaskState::Deferred => ("defer", "DEFER", "Deferred", "deferred"),
+    }
+}
+
+// Checking a task stamps it with done_stamp (when stamping is enabled);
+// moving it to any other state removes its done:... stamp.
+fn update_task_state(
+    task: &Task,
+    state: TaskState,
+    done_stamp: Option<&str>,
+    today: NaiveDate,
+) -> Task {
+    let mut updated_task = task.clone();
+    updated_task.state = state;
+
+    let stamp = match state {
+        TaskState::Done => done_stamp,
+        _ => None,
+    };
+
+    if task.format == FileFormat::TodoTxt {
+        // the completion date of a todo.txt task comes after its "x" mark, and
+        // is required when the task has a creation date
+        updated_task.done_date = if state_is_closed(state) {
+            stamp
+                .and_then(|stamp| NaiveDate::parse_from_str(stamp.get(..10)?, "%Y-%m-%d").ok())
+                .or(task.created.map(|_| today))
+        } else {
+            None
+        };
+    } else if stamp.is_some() || task.done_date.is_some() {
+        updated_task.name = text_set_done(&task.name, stamp);
+        updated_task.plain_name = text_set_done(&task.plain_name, stamp);
+        updated_task.done_date = text_get_done_date(&updated_task.plain_name);
+    }
+
+    updated_task.line = task_to_line(&updated_task);
+    updated_task
+}
+
+// Closing a task closes its whole open subtree; checking a task also
+// completes the ancestors whose children are then all closed. Reopening or
+// starting a task reopens its closed ancestors.
+fn get_related_tasks_to_update(
+    tasks: &[Task],
+    task: &Task,
+    state: TaskState,
+) -> Vec<(Task, TaskState)> {
+    let mut related_tasks: Vec<(Task, TaskState)> = Vec::new();
+
+    match state {
+        TaskState::Deferred => return related_tasks,
+        TaskState::Open | TaskState::InProgress => {
+            return get_task_ancestors(tasks, task)
+                .into_iter()
+                .filter(|t| state_is_closed(t.state))
+                .map(|t| (t, TaskState::Open))
+                .collect();
+        }
+        TaskState::Done | TaskState::Cancelled => (),
+    }
+
+    let mut closed_nums: Vec<usize> = vec![task.num];
+
+    for descendant in get_task_descendants(tasks, task) {
+        closed_nums.push(descendant.num);
+        if !state_is_closed(descendant.state) {
+            related_tasks.push((descendant, state));
+        }
+    }
+
+    if state == TaskState::Cancelled {
+        return related_tasks;
+    }
+
+    for ancestor in get_task_ancestors(tasks, task) {
+        if state_is_closed(ancestor.state) {
+            break;
+        }
+
+        let all_children_closed = ancestor.children.iter().all(|child_num| {
+            state_is_closed(tasks[child_num - 1].state) || closed_nums.contains(child_num)
+        });
+
+        if !all_children_closed {
+            break;
+        }
+
+        closed_nums.push(ancestor.num);
+        related_tasks.push((ancestor, TaskState::Done));
+    }
+
+    related_tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Stamp;
+    use crate::test_helpers::test::{
+        now_getter_fixed, ContentGetterMock, ContentSetterMock, StringOutputerMock,
+        UserCmdRunnerMock,
+    };
+
+    fn get_nested_test_contents() -> String {
+        [
+            "- [ ] Parent",
+            "  - [ ] Child 1",
+            "    - [ ] Grandchild",
+            "  - [x] Child 2",
+            "- [ ] Other",
+        ]
+        .join("\n")
+    }
+
+    #[test]
+    fn test_cmd_check_parent_checks_subtree() {
+        let mut string_outputer = StringOutputerMock::new();
+        let content_getter = ContentGetterMock::new(Ok(get_nested_test_contents()));
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        let user_cmd_runner = UserCmdRunnerMock::new();
+        let task_formatter = TaskFormatter::new(false);
+
+        cmd(
+            &mut string_outputer,
+            &content_getter,
+            &mut content_setter,
+            &user_cmd_runner,
+            &task_formatter,
+            &Settings::default(),
+            now_getter_fixed(),
+            0,
+            1,
+            TaskState::Done,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content_setter.content,
+            Some(String::from(
+                "- [x] Parent\n  - [x] Child 1\n    - [x] Grandchild\n  - [x] Child 2\n- [ ] Other"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_cmd_check_non_existent() {
+        let mut string_outputer = StringOutputerMock::new();
+        let content_getter = ContentGetterMock::new(Ok(get_nested_test_contents()));
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+
+        for (rank, state) in [
+            (0, TaskState::Done),
+            (0, TaskState::InProgress),
+            (6, TaskState::Done),
+        ] {
+            assert_eq!(
+                cmd(
+                    &mut string_outputer,
+                    &content_getter,
+                    &mut content_setter,
+                    &UserCmdRunnerMock::new(),
+                    &TaskFormatter::new(false),
+                    &Settings::default(),
+                    now_getter_fixed(),
+                    0,
+                    rank,
+                    state,
+                ),
+                Err(format!("Non existent task {}", rank))
+            );
+        }
+        assert_eq!(content_setter.content, None);
+    }
+
+    #[test]
+    fn test_cmd_check_last_child_completes_parent() {
+        let mut string_outputer = StringOutputerMock::new();
+        let content_getter = ContentGetterMock::new(Ok(get_nested_test_contents()));
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        let user_cmd_runner = UserCmdRunnerMock::new();
+        let task_formatter = TaskFormatter::new(false);
+
+        cmd(
+            &mut string_outputer,
+            &content_getter,
+            &mut content_setter,
+            &user_cmd_runner,
+            &task_formatter,
+            &Settings::default(),
+            now_getter_fixed(),
+            0,
+            3,
+            TaskState::Done,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content_setter.content,
+            Some(String::from(
+                "- [x] Parent\n  - [x] Child 1\n    - [x] Grandchild\n  - [x] Child 2\n- [ ] Other"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_cmd_uncheck_reopens_ancestors() {
+        let mut string_outputer = StringOutputerMock::new();
+        let content_getter =
+            ContentGetterMock::new(Ok(String::from("- [x] Parent\n  - [x] Child")));
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        let user_cmd_runner = UserCmdRunnerMock::new();
+        let task_formatter = TaskFormatter::new(false);
+
+        cmd(
+            &mut string_outputer,
+            &content_getter,
+            &mut content_setter,
+            &user_cmd_runner,
+            &task_formatter,
+            &Settings::default(),
+            now_getter_fixed(),
+            0,
+            2,
+            TaskState::Open,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content_setter.content,
+            Some(String::from("- [ ] Parent\n  - [ ] Child"))
+        );
+    }
+
+    #[test]
+    fn test_cmd_check_recurring() {
+        let task_formatter = TaskFormatter::new(false);
+        let contents =
+            String::from("- [ ] Chores every:week due:2020-06-15\n  - [ ] Vacuum\n- [ ] Other");
+
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new(Ok(contents.clone())),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &task_formatter,
+            &Settings::default(),
+            now_getter_fixed(),
+            0,
+            1,
+            TaskState::Done,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content_setter.content,
+            Some(String::from(
+                "- [x] Chores every:week due:2020-06-15\n  - [x] Vacuum\n- [ ] Chores every:week due:2020-06-22\n- [ ] Other"
+            ))
+        );
+
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new(Ok(contents)),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &task_formatter,
+            &Settings {
+                recur_mode: RecurMode::Replace,
+                ..Default::default()
+            },
+            now_getter_fixed(),
+            0,
+            1,
+            TaskState::Done,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content_setter.content,
+            Some(String::from(
+                "- [ ] Chores every:week due:2020-06-22\n  - [x] Vacuum\n- [ ] Other"
+            ))
+        );
+
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new(Ok(String::from("- [ ] Chores every:week due:2020-06-15"))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &task_formatter,
+            &Settings::default(),
+            now_getter_fixed(),
+            0,
+            1,
+            TaskState::Cancelled,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content_setter.content,
+            Some(String::from("- [-] Chores every:week due:2020-06-15"))
+        );
+    }
+
+    #[test]
+    fn test_cmd_check_recurring_with_id() {
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new(Ok(String::from(
+                "- [ ] Chores every:week due:2020-06-15 ^ab12\n- [ ] Other",
+            ))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            &Settings::default(),
+            now_getter_fixed(),
+            0,
+            1,
+            TaskState::Done,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content_setter.content,
+            Some(String::from(
+                "- [x] Chores every:week due:2020-06-15 ^ab12\n- [ ] Chores every:week due:2020-06-22 ^7iyc\n- [ ] Other"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_cmd_cancel_subtree() {
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new(Ok(get_nested_test_contents())),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            &Settings::default(),
+            now_getter_fixed(),
+            0,
+            2,
+            TaskState::Cancelled,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content_setter.content,
+            Some(String::from(
+                "- [ ] Parent\n  - [-] Child 1\n    - [-] Grandchild\n  - [x] Child 2\n- [ ] Other"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_cmd_start_reopens_ancestors() {
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new(Ok(String::from("- [x] Parent\n  - [X] Child"))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            &Settings::default(),
+            now_getter_fixed(),
+            0,
+            2,
+            TaskState::InProgress,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content_setter.content,
+            Some(String::from("- [ ] Parent\n  - [/] Child"))
+        );
+    }
+
+    #[test]
+    fn test_cmd_check_stamps_done() {
+        let settings = Settings {
+            stamp_done: Stamp::DateTime,
+            ..Default::default()
+        };
+
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new(Ok(String::from("- [ ] Parent\n  - [ ] Child // note"))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            &settings,
+            now_getter_fixed(),
+            0,
+            2,
+            TaskState::Done,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content_setter.content,
+            Some(String::from(
+                "- [x] Parent done:2020-06-15T09:30\n  - [x] Child done:2020-06-15T09:30 // note"
+            ))
+        );
+
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new(Ok(String::from(
+                "- [x] Parent done:2020-06-15\n  - [x] Child done:2020-06-15",
+            ))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            &settings,
+            now_getter_fixed(),
+            0,
+            2,
+            TaskState::Open,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content_setter.content,
+            Some(String::from("- [ ] Parent\n  - [ ] Child"))
+        );
+    }
+
+    #[test]
+    fn test_cmd_check_unblocks() {
+        let mut string_outputer = StringOutputerMock::new();
+        cmd(
+            &mut string_outputer,
+            &ContentGetterMock::new(Ok(String::from(
+                "- [ ] Deploy after:^ab12\n- [ ] Write code ^ab12\n  - [ ] Write tests",
+            ))),
+            &mut ContentSetterMock::new(Ok(())),
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            &Settings::default(),
+            now_getter_fixed(),
+            0,
+            2,
+            TaskState::Done,
+        )
+        .unwrap();
+
+        assert_eq!(
+            string_outputer.get_info(),
+            "Checked: [2] Write code ^ab12\nChecked: [3] Write tests\nUnblocked: [1] Deploy after:^ab12\n"
+        );
+    }
+
+    #[test]
+    fn test_cmd_check_org() {
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new_org(Ok(String::from(
+                "* Job\n** TODO Release\n   DEADLINE: <2020-06-20 Sat>\n   - [ ] Tag it\n** TODO Other",
+            ))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            &Settings::default(),
+            now_getter_fixed(),
+            0,
+            1,
+            TaskState::Done,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content_setter.content,
+            Some(String::from(
+                "* Job\n** DONE Release\n   DEADLINE: <2020-06-20 Sat>\n   - [X] Tag it\n** TODO Other"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_cmd_check_todotxt() {
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new_todotxt(Ok(String::from(
+                "(A) 2020-06-01 Release +job\nFix the roof",
+            ))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            &Settings::default(),
+            now_getter_fixed(),
+            0,
+            1,
+            TaskState::Done,
+        )
+        .unwrap();
+
+        // a creation date requires a completion date
+        assert_eq!(
+            content_setter.content,
+            Some(String::from(
+                "x 2020-06-15 (A) 2020-06-01 Release +job\nFix the roof"
+            ))
+        );
+
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new_todotxt(Ok(String::from(
+                "x 2020-06-15 (A) 2020-06-01 Release +job",
+            ))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            &Settings::default(),
+            now_getter_fixed(),
+            0,
+            1,
+            TaskState::InProgress,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content_setter.content,
+            Some(String::from("(A) 2020-06-01 Release +job state:started"))
+        );
+    }
+}
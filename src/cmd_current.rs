@@ -5,9 +5,9 @@ pub fn cmd(
     outputer: &mut dyn StringOutputer,
     content_getter: &dyn ContentGetter,
     task_formatter: &TaskFormatter,
-    cycle: bool,
+    cycle_minutes: Option<u64>,
 ) -> Result<(), String> {
-    if let Ok(Some((task, use_sections))) = get_current_task(content_getter, cycle) {
+    if let Ok(Some((task, use_sections))) = get_current_task(content_getter, task_formatter.today, cycle_minutes) {
         outputer.info(&task_formatter.display_numbered_task(&task, use_sections, false))
     }
 
@@ -18,19 +18,23 @@ pub fn cmd(
 mod tests {
 
     use super::*;
-    use crate::test_helpers::test::{get_std_test_contents, ContentGetterMock, StringOutputerMock};
+    use crate::test_helpers::test::{
+        get_std_test_contents, today_getter_fixed, ContentGetterMock, StringOutputerMock,
+    };
 
     #[test]
     fn test_cmd_current() {
         let task_formatter = &TaskFormatter {
             supports_colors: false,
+            today: today_getter_fixed(),
+            tag_colors: Vec::new(),
         };
         // Empty contents
         {
             let outputer_mock = &mut StringOutputerMock::new();
             let content_getter_mock = &ContentGetterMock::new(Ok("".to_string()));
 
-            cmd(outputer_mock, content_getter_mock, task_formatter, false).unwrap();
+            cmd(outputer_mock, content_getter_mock, task_formatter, None).unwrap();
             assert_eq!(outputer_mock.get_info(), "");
         }
 
@@ -40,7 +44,7 @@ mod tests {
             let (test_contents, _) = get_std_test_contents();
             let content_getter_mock = &ContentGetterMock::new(Ok(test_contents));
 
-            cmd(outputer_mock, content_getter_mock, task_formatter, false).unwrap();
+            cmd(outputer_mock, content_getter_mock, task_formatter, None).unwrap();
             assert_eq!(
                 outputer_mock.get_info(),
                 "[2] Standard unchecked focused\n"
@@ -0,0 +1,78 @@
+use crate::services::{ContentGetter, ContentSetter, StringOutputer, TaskFormatter, UserCmdRunner};
+use crate::tasks::{get_all_tasks, get_task_line_nums, text_remove_lines_in_str};
+
+pub fn cmd(
+    outputer: &mut dyn StringOutputer,
+    content_getter: &dyn ContentGetter,
+    content_setter: &mut dyn ContentSetter,
+    user_cmd_runner: &dyn UserCmdRunner,
+    task_formatter: &TaskFormatter,
+    rank_one_based: usize,
+) -> Result<(), String> {
+    let (tasks, use_sections, _, _) = get_all_tasks(content_getter)?;
+    if rank_one_based == 0 || rank_one_based > tasks.len() {
+        return Err(format!("Non existent task {}", rank_one_based));
+    }
+
+    let task = &tasks[rank_one_based - 1];
+
+    // the task is removed along with its body and its subtasks
+    let deleted_content = text_remove_lines_in_str(
+        &content_getter.get_contents()?,
+        get_task_line_nums(&tasks, task),
+    )?;
+
+    content_setter.set_contents(deleted_content)?;
+
+    outputer.info(&format!(
+        "Deleted: {}",
+        task_formatter.display_numbered_task(task, use_sections, true)
+    ));
+
+    match user_cmd_runner.build("delete", "DELETE", &format!("Deleted \"{}\"", task.name)) {
+        Ok(Some(mut cmd)) => {
+            user_cmd_runner.run(user_cmd_runner.env_single_task(task, &mut cmd))?;
+        }
+        Ok(None) => (),
+        Err(e) => return Err(e),
+    };
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test::{
+        ContentGetterMock, ContentSetterMock, StringOutputerMock, UserCmdRunnerMock,
+    };
+
+    #[test]
+    fn test_cmd_delete_with_body() {
+        let mut string_outputer = StringOutputerMock::new();
+        let content_getter = ContentGetterMock::new(Ok([
+            "- [ ] First",
+            "  Notes of first",
+            "  - [ ] Subtask",
+            "    Notes of subtask",
+            "",
+            "- [ ] Second",
+        ]
+        .join("\n")));
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        let user_cmd_runner = UserCmdRunnerMock::new();
+        let task_formatter = TaskFormatter::new(false);
+
+        cmd(
+            &mut string_outputer,
+            &content_getter,
+            &mut content_setter,
+            &user_cmd_runner,
+            &task_formatter,
+            1,
+        )
+        .unwrap();
+
+        assert_eq!(content_setter.content, Some(String::from("\n- [ ] Second")));
+    }
+}
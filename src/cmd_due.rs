@@ -0,0 +1,103 @@
+use crate::services::{ContentGetter, StringOutputer, TaskFormatter};
+use crate::tasks::{get_open_tasks, sort_tasks_by_due};
+
+pub fn cmd(
+    outputer: &mut dyn StringOutputer,
+    content_getter: &dyn ContentGetter,
+    task_formatter: &TaskFormatter,
+    check: bool,
+) -> Result<(), String> {
+    let (open_tasks, use_sections, _, _) = get_open_tasks(content_getter)?;
+
+    let due_tasks: Vec<_> = sort_tasks_by_due(&open_tasks)
+        .into_iter()
+        .filter(|t| t.due.is_some())
+        .collect();
+
+    if !check {
+        for task in &due_tasks {
+            outputer.info(&task_formatter.display_numbered_task(task, use_sections, true));
+        }
+
+        return Ok(());
+    }
+
+    let overdue_tasks: Vec<_> = due_tasks
+        .iter()
+        .filter(|t| t.due.is_some_and(|due| due < task_formatter.today))
+        .collect();
+
+    if overdue_tasks.is_empty() {
+        return Ok(());
+    }
+
+    for task in &overdue_tasks {
+        outputer.info(&task_formatter.display_numbered_task(task, use_sections, true));
+    }
+
+    Err(format!(
+        "{} overdue task{}",
+        overdue_tasks.len(),
+        if overdue_tasks.len() > 1 { "s" } else { "" }
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test::{today_getter_fixed, ContentGetterMock, StringOutputerMock};
+
+    fn get_due_test_contents() -> String {
+        [
+            "- [ ] No due date",
+            "- [ ] Later due:2020-07-01",
+            "- [ ] Overdue due:2020-06-01",
+            "- [x] Done overdue due:2020-05-01",
+            "- [ ] Due today due:2020-06-15",
+        ]
+        .join("\n")
+    }
+
+    #[test]
+    fn test_cmd_due() {
+        let task_formatter = &TaskFormatter {
+            supports_colors: false,
+            today: today_getter_fixed(),
+            tag_colors: Vec::new(),
+        };
+        let outputer_mock = &mut StringOutputerMock::new();
+        let content_getter_mock = &ContentGetterMock::new(Ok(get_due_test_contents()));
+
+        cmd(outputer_mock, content_getter_mock, task_formatter, false).unwrap();
+        assert_eq!(
+            outputer_mock.get_info(),
+            "[3] Overdue due:2020-06-01\n[5] Due today due:2020-06-15\n[2] Later due:2020-07-01\n"
+        );
+    }
+
+    #[test]
+    fn test_cmd_due_check() {
+        let task_formatter = &TaskFormatter {
+            supports_colors: false,
+            today: today_getter_fixed(),
+            tag_colors: Vec::new(),
+        };
+        let outputer_mock = &mut StringOutputerMock::new();
+        let content_getter_mock = &ContentGetterMock::new(Ok(get_due_test_contents()));
+
+        assert_eq!(
+            cmd(outputer_mock, content_getter_mock, task_formatter, true),
+            Err(String::from("1 overdue task"))
+        );
+        assert_eq!(outputer_mock.get_info(), "[3] Overdue due:2020-06-01\n");
+
+        let outputer_mock = &mut StringOutputerMock::new();
+        let content_getter_mock =
+            &ContentGetterMock::new(Ok(String::from("- [ ] Due today due:2020-06-15")));
+
+        assert_eq!(
+            cmd(outputer_mock, content_getter_mock, task_formatter, true),
+            Ok(())
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::services::{TaxfilePathGetter, UserCmdRunner};
+use crate::services::{get_aggregated_paths, TaxfilePathGetter, UserCmdRunner};
 use std::env;
 use std::process::Command;
 
@@ -7,6 +7,8 @@ pub fn cmd(
     user_cmd_runner: &dyn UserCmdRunner,
 ) -> Result<(), String> {
     let str_file_path = taxfile_path_getter.get_taxfile_path().unwrap();
+    // the files of an aggregated task list are all opened
+    let file_paths = get_aggregated_paths(&str_file_path)?.unwrap_or_else(|| vec![str_file_path]);
 
     let res = env::var("EDITOR");
     if res.is_err() {
@@ -17,7 +19,7 @@ pub fn cmd(
 
     let editor = res.unwrap();
 
-    if let Ok(mut child) = Command::new(editor).arg(str_file_path).spawn() {
+    if let Ok(mut child) = Command::new(editor).args(file_paths).spawn() {
         match child.wait() {
             Ok(_) => {
                 match user_cmd_runner.build("edit", "EDIT", "Manually edited tasks") {
@@ -1,5 +1,7 @@
 use crate::services::{ContentGetter, ContentSetter, StringOutputer, TaskFormatter, UserCmdRunner};
-use crate::tasks::{get_all_tasks, task_to_markdown, text_add_focus, text_replace_line_in_str};
+use crate::tasks::{
+    get_all_tasks, state_is_closed, task_to_line, text_add_focus, text_replace_line_in_str,
+};
 
 pub fn cmd(
     outputer: &mut dyn StringOutputer,
@@ -11,13 +13,13 @@ pub fn cmd(
     focus: bool,
 ) -> Result<(), String> {
     let (tasks, use_sections, _, _) = get_all_tasks(content_getter)?;
-    if rank_one_based > tasks.len() {
+    if rank_one_based == 0 || rank_one_based > tasks.len() {
         return Err(format!("Non existent task {}", rank_one_based));
     }
 
     let task = &tasks[rank_one_based - 1];
 
-    if task.is_checked {
+    if state_is_closed(task.state) {
         outputer.info(&format!(
             "Task is completed, cannot proceed: {}",
             task_formatter.display_numbered_task(task, use_sections, true)
@@ -42,12 +44,12 @@ pub fn cmd(
     let mut updated_task = task.clone();
     updated_task.is_focused = focus;
     updated_task.name = if focus {
-        text_add_focus(task.plain_name.as_str())
+        text_add_focus(task.plain_name.as_str(), task.format)
     } else {
         task.plain_name.clone()
     };
 
-    updated_task.line = task_to_markdown(&updated_task);
+    updated_task.line = task_to_line(&updated_task);
 
     let replaced_content = text_replace_line_in_str(
         &content_getter.get_contents()?,
@@ -82,3 +84,84 @@ pub fn cmd(
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test::{
+        ContentGetterMock, ContentSetterMock, StringOutputerMock, UserCmdRunnerMock,
+    };
+
+    #[test]
+    fn test_cmd_focus_non_existent() {
+        let mut string_outputer = StringOutputerMock::new();
+        let content_getter = ContentGetterMock::new(Ok(String::from("- [ ] Some task")));
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+
+        assert_eq!(
+            cmd(
+                &mut string_outputer,
+                &content_getter,
+                &mut content_setter,
+                &UserCmdRunnerMock::new(),
+                &TaskFormatter::new(false),
+                0,
+                true,
+            ),
+            Err(String::from("Non existent task 0"))
+        );
+        assert_eq!(content_setter.content, None);
+    }
+
+    #[test]
+    fn test_cmd_focus_todotxt() {
+        let mut string_outputer = StringOutputerMock::new();
+        let content_getter = ContentGetterMock::new_todotxt(Ok(String::from(
+            "Fix the roof +home @ladder due:2020-06-20 ^ab12\nBuy milk +home",
+        )));
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+
+        cmd(
+            &mut string_outputer,
+            &content_getter,
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            1,
+            true,
+        )
+        .unwrap();
+
+        // the task stays in the section of its +project
+        assert_eq!(
+            content_setter.content,
+            Some(String::from(
+                "**Fix the roof** +home @ladder due:2020-06-20 ^ab12\nBuy milk +home"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_cmd_focus_org() {
+        let mut string_outputer = StringOutputerMock::new();
+        let content_getter =
+            ContentGetterMock::new_org(Ok(String::from("* Job\n** TODO [#A] Release :work:")));
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+
+        cmd(
+            &mut string_outputer,
+            &content_getter,
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            1,
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content_setter.content,
+            Some(String::from("* Job\n** TODO [#A] *Release* :work:"))
+        );
+    }
+}
@@ -1,6 +1,12 @@
+use crate::model::FileFormat;
 use crate::services::{ContentGetter, ContentSetter, StringOutputer, UserCmdRunner};
-use crate::tasks::{get_all_tasks, search_section, section_to_markdown, text_replace_line_in_str};
+use crate::tasks::{
+    get_all_tasks, search_section, section_to_line, text_replace_line_in_str,
+    text_set_todotxt_focus,
+};
 
+// Several sections can be focused at once; focusing a section with `only`
+// blurs the other focused sections
 pub fn cmd(
     outputer: &mut dyn StringOutputer,
     content_getter: &dyn ContentGetter,
@@ -8,15 +14,27 @@ pub fn cmd(
     _user_cmd_runner: &dyn UserCmdRunner,
     section_name: String,
     focus: bool,
+    only: bool,
 ) -> Result<(), String> {
-    let (_, _, sections, _) = get_all_tasks(content_getter)?;
+    let (_, _, sections, focused_sections) = get_all_tasks(content_getter)?;
 
     let section = match search_section(section_name.as_ref(), &sections) {
         None => return Err(format!("Section not found: {}", section_name)),
         Some(section) => section,
     };
 
-    if focus && section.is_focused {
+    // the section of a file of an aggregated task list has no heading to mark
+    if section.level == 0 {
+        return Err(format!(
+            "Cannot {} a whole task file: {}",
+            if focus { "focus" } else { "blur" },
+            section.plain_name
+        ));
+    }
+
+    let blurs_others = only && focused_sections.iter().any(|s| s.num != section.num);
+
+    if focus && section.is_focused && !blurs_others {
         outputer.info(&format!("Already focused: {}", section.plain_name));
         return Ok(());
     } else if !focus && !section.is_focused {
@@ -24,34 +42,50 @@ pub fn cmd(
         return Ok(());
     }
 
-    let mut updated_section = section.clone();
-    updated_section.is_focused = focus;
-    updated_section.line = section_to_markdown(&updated_section);
-
     outputer.info(&format!(
         "{}: {}",
         if focus { "Focused" } else { "Blurred" },
         &section.plain_name
     ));
 
+    // todo.txt projects have no heading line to mark
+    if section.format == FileFormat::TodoTxt {
+        let mut focused_projects: Vec<String> = focused_sections
+            .iter()
+            .filter(|s| s.num != section.num && !only)
+            .map(|s| s.name.clone())
+            .collect();
+        if focus {
+            focused_projects.push(section.name.clone());
+        }
+
+        return content_setter.set_contents(text_set_todotxt_focus(
+            &content_getter.get_contents()?,
+            &focused_projects,
+        ));
+    }
+
+    let mut updated_section = section.clone();
+    updated_section.is_focused = focus;
+    updated_section.line = section_to_line(&updated_section);
+
     let mut replaced_content = text_replace_line_in_str(
         &content_getter.get_contents()?,
         section.line_num,
         &updated_section.line,
     );
 
-    // blur other focused sections
-
-    for section_rc in sections {
-        let section_cur = section_rc.as_ref();
-        if section_cur.num == section.num {
-            continue;
-        }
+    if only {
+        // blur other focused sections
+        for section_rc in focused_sections {
+            let section_cur = section_rc.as_ref();
+            if section_cur.num == section.num {
+                continue;
+            }
 
-        if section_cur.is_focused {
             let mut updated_section = section_cur.clone();
             updated_section.is_focused = false;
-            let updated_line = section_to_markdown(&updated_section);
+            let updated_line = section_to_line(&updated_section);
 
             replaced_content =
                 text_replace_line_in_str(&replaced_content, updated_section.line_num, &updated_line)
@@ -60,3 +94,75 @@ pub fn cmd(
 
     content_setter.set_contents(replaced_content)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test::{
+        ContentGetterMock, ContentSetterMock, StringOutputerMock, UserCmdRunnerMock,
+    };
+
+    #[test]
+    fn test_cmd_focus_section() {
+        let content = "# **Job**\n- [ ] Send the invoice\n# Oncall\n# Perso\n";
+
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new(Ok(String::from(content))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            String::from("Oncall"),
+            true,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            content_setter.content,
+            Some(String::from(
+                "# **Job**\n- [ ] Send the invoice\n# **Oncall**\n# Perso\n"
+            ))
+        );
+
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new(Ok(String::from(content))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            String::from("Oncall"),
+            true,
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            content_setter.content,
+            Some(String::from(
+                "# Job\n- [ ] Send the invoice\n# **Oncall**\n# Perso\n"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_cmd_focus_section_todotxt() {
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new_todotxt(Ok(String::from(
+                "# focus: +job\nSend the invoice +job\nFix the roof +home\n",
+            ))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            String::from("home"),
+            true,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            content_setter.content,
+            Some(String::from(
+                "# focus: +job +home\nSend the invoice +job\nFix the roof +home\n"
+            ))
+        );
+    }
+}
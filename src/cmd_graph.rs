@@ -0,0 +1,91 @@
+use crate::model::Task;
+use crate::services::{ContentGetter, StringOutputer};
+use crate::tasks::{get_all_tasks, get_task_blockers, state_is_closed};
+
+// Prints the dependency graph of the tasks in the Graphviz DOT format
+pub fn cmd(
+    outputer: &mut dyn StringOutputer,
+    content_getter: &dyn ContentGetter,
+) -> Result<(), String> {
+    let (tasks, _, _, _) = get_all_tasks(content_getter)?;
+
+    // (blocker num, blocked num)
+    let mut edges: Vec<(usize, usize)> = Vec::new();
+    for task in &tasks {
+        for blocker in get_task_blockers(&tasks, task) {
+            edges.push((blocker.num, task.num));
+        }
+    }
+
+    outputer.info("digraph tasks {");
+
+    for task in tasks.iter().filter(|t| {
+        edges
+            .iter()
+            .any(|(from, to)| *from == t.num || *to == t.num)
+    }) {
+        outputer.info(&format!(
+            "    t{} [label=\"{}\"{}];",
+            task.num,
+            dot_escape(&format!("[{}] {}", task.num, task.plain_name)),
+            get_node_style(task)
+        ));
+    }
+
+    for (from, to) in edges {
+        outputer.info(&format!("    t{} -> t{};", from, to));
+    }
+
+    outputer.info("}");
+
+    Ok(())
+}
+
+fn get_node_style(task: &Task) -> &'static str {
+    if state_is_closed(task.state) {
+        ", style=dashed"
+    } else if task.is_blocked {
+        ", color=red"
+    } else {
+        ""
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test::{ContentGetterMock, StringOutputerMock};
+
+    #[test]
+    fn test_cmd_graph() {
+        let mut outputer = StringOutputerMock::new();
+        let content_getter = ContentGetterMock::new(Ok([
+            "- [ ] Deploy after:^ab12 after:^cd34",
+            "- [ ] Write \"the\" code ^ab12",
+            "- [x] Review ^cd34",
+            "- [ ] Unrelated",
+        ]
+        .join("\n")));
+
+        cmd(&mut outputer, &content_getter).unwrap();
+
+        assert_eq!(
+            outputer.get_info(),
+            [
+                "digraph tasks {",
+                "    t1 [label=\"[1] Deploy after:^ab12 after:^cd34\", color=red];",
+                "    t2 [label=\"[2] Write \\\"the\\\" code ^ab12\"];",
+                "    t3 [label=\"[3] Review ^cd34\", style=dashed];",
+                "    t2 -> t1;",
+                "    t3 -> t1;",
+                "}",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -1,28 +1,57 @@
+use crate::model::Section;
 use crate::services::{ContentGetter, StringOutputer, TaskFormatter};
-use crate::tasks::{filter_tasks_in_section, get_open_tasks};
+use crate::tasks::{
+    filter_tasks_in_sections, filter_tasks_with_context, filter_tasks_with_tag, get_open_tasks,
+    sort_tasks_by_due, sort_tasks_by_priority, text_format_age,
+};
+use std::rc::Rc;
+
+pub enum ListSort {
+    Priority,
+    Due,
+}
+
+#[derive(Default)]
+pub struct ListOptions {
+    pub all: bool,
+    pub tag: Option<String>,
+    pub context: Option<String>,
+    pub sort: Option<ListSort>,
+    pub long: bool,
+}
 
 pub fn cmd(
     outputer: &mut dyn StringOutputer,
     content_getter: &dyn ContentGetter,
     task_formatter: &TaskFormatter,
-    all: bool,
+    options: &ListOptions,
 ) -> Result<(), String> {
-    let (open_tasks, use_sections, _, focused_section) = get_open_tasks(content_getter)?;
+    let (mut open_tasks, use_sections, _, focused_sections) = get_open_tasks(content_getter)?;
+
+    if let Some(tag) = &options.tag {
+        open_tasks = filter_tasks_with_tag(&open_tasks, tag);
+    }
+
+    if let Some(context) = &options.context {
+        open_tasks = filter_tasks_with_context(&open_tasks, context);
+    }
 
     let mut other_tasks_hint: Option<String> = None;
 
-    let filtered_tasks = if !all && use_sections && focused_section != None {
-        let focused_section_unwrapped = focused_section.unwrap();
-        let focused_section_ref = focused_section_unwrapped.as_ref();
-        let ftasks = filter_tasks_in_section(&open_tasks, focused_section_ref);
+    let filtered_tasks = if !focused_sections.is_empty() && !options.all && use_sections {
+        let ftasks = filter_tasks_in_sections(&open_tasks, &focused_sections);
         {
             let nb_diff = open_tasks.len() - ftasks.len();
             other_tasks_hint = if nb_diff > 0 {
                 Some(format!(
-                    "\n{} other open task{} outside of \"{}\".",
+                    "\n{} other open task{} outside of {}.",
                     nb_diff,
                     if nb_diff > 1 { "s" } else { "" },
-                    focused_section_ref.plain_name,
+                    focused_sections
+                        .iter()
+                        .map(|s| format!("\"{}\"", s.plain_name))
+                        .collect::<Vec<String>>()
+                        .join(" and "),
                 ))
             } else {
                 None
@@ -33,18 +62,42 @@ pub fn cmd(
         open_tasks
     };
 
+    let mut filtered_tasks = match &options.sort {
+        None => filtered_tasks,
+        Some(ListSort::Priority) => sort_tasks_by_priority(&filtered_tasks),
+        Some(ListSort::Due) => sort_tasks_by_due(&filtered_tasks),
+    };
+    // tasks stay grouped by section, even when the tasks of a todo.txt
+    // project are scattered across the file
+    filtered_tasks.sort_by_key(|t| t.section.as_ref().map(|s| s.num));
+
     let mut section_num = 0;
+    let mut displayed_section_nums: Vec<usize> = Vec::new();
     for task in filtered_tasks {
         if use_sections {
             // Display section header once
             match &task.section {
-                None => (),
-                Some(rc) => {
-                    let section = rc.as_ref();
-                    if section_num != section.num {
+                Some(rc) if section_num != rc.num => {
+                    // headers of the enclosing sections come first
+                    let mut headers: Vec<Rc<Section>> = Vec::new();
+                    let mut cur = Some(rc.clone());
+                    while let Some(section) = cur {
+                        if displayed_section_nums.contains(&section.num) {
+                            break;
+                        }
+                        cur = section.parent.clone();
+                        headers.push(section);
+                    }
+
+                    for (index, section) in headers.iter().rev().enumerate() {
                         outputer.info(&format!(
-                            "{}{} {}",
-                            if section_num == 0 { "" } else { "\n" },
+                            "{}{}{} {}",
+                            if section_num == 0 || index > 0 {
+                                ""
+                            } else {
+                                "\n"
+                            },
+                            "  ".repeat(section.depth),
                             if section.is_focused {
                                 task_formatter.display_bold_color_only("#")
                             } else {
@@ -56,14 +109,43 @@ pub fn cmd(
                                 section.plain_name.clone()
                             }
                         ));
-
-                        section_num = section.num;
+                        displayed_section_nums.push(section.num);
                     }
+
+                    section_num = rc.num;
                 }
+                _ => (),
             }
         }
 
-        outputer.info(&task_formatter.display_numbered_task(&task, false, true)); // false: disable inline section name, as header is displayed in list
+        // age of the task, when its creation date is known
+        let age = match task.created {
+            Some(created) if options.long => format!(
+                " {}",
+                task_formatter.display_dimmed(&format!(
+                    "({} old)",
+                    text_format_age(created, task_formatter.today)
+                ))
+            ),
+            _ => String::from(""),
+        };
+
+        outputer.info(&format!(
+            "{}{}{}",
+            // subtasks are indented under their parent, unless tasks are sorted
+            "  ".repeat(
+                if options.sort.is_none() {
+                    task.depth
+                } else {
+                    0
+                } + match &task.section {
+                    Some(section) if use_sections => section.depth,
+                    _ => 0,
+                }
+            ),
+            task_formatter.display_numbered_task(&task, false, true), // false: disable inline section name, as header is displayed in list
+            age
+        ));
     }
 
     match other_tasks_hint {
@@ -77,19 +159,29 @@ pub fn cmd(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_helpers::test::{get_std_test_contents, ContentGetterMock, StringOutputerMock};
+    use crate::test_helpers::test::{
+        get_std_test_contents, today_getter_fixed, ContentGetterMock, StringOutputerMock,
+    };
 
     #[test]
     fn test_cmd_list() {
         let task_formatter = &TaskFormatter {
             supports_colors: false,
+            today: today_getter_fixed(),
+            tag_colors: Vec::new(),
         };
         // Empty contents
         {
             let outputer_mock = &mut StringOutputerMock::new();
             let content_getter_mock = &ContentGetterMock::new(Ok("".to_string()));
 
-            cmd(outputer_mock, content_getter_mock, task_formatter, false).unwrap();
+            cmd(
+                outputer_mock,
+                content_getter_mock,
+                task_formatter,
+                &ListOptions::default(),
+            )
+            .unwrap();
             assert_eq!(outputer_mock.get_info(), "");
         }
 
@@ -99,11 +191,233 @@ mod tests {
             let (test_contents, _) = get_std_test_contents();
             let content_getter_mock = &ContentGetterMock::new(Ok(test_contents));
 
-            cmd(outputer_mock, content_getter_mock, task_formatter, false).unwrap();
+            cmd(
+                outputer_mock,
+                content_getter_mock,
+                task_formatter,
+                &ListOptions::default(),
+            )
+            .unwrap();
             assert_eq!(
                 outputer_mock.get_info(),
                 "[1] Standard unchecked\n[2] **Standard unchecked focused**\n[5] Standard unchecked\n[6] **Standard unchecked focused**\n"
             );
         }
     }
+
+    #[test]
+    fn test_cmd_list_nested() {
+        let task_formatter = &TaskFormatter {
+            supports_colors: false,
+            today: today_getter_fixed(),
+            tag_colors: Vec::new(),
+        };
+        let outputer_mock = &mut StringOutputerMock::new();
+        let content_getter_mock = &ContentGetterMock::new(Ok(String::from(
+            "- [ ] Parent\n  - [ ] Child\n  - [x] Done child\n    - [ ] Grandchild",
+        )));
+
+        cmd(
+            outputer_mock,
+            content_getter_mock,
+            task_formatter,
+            &ListOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            outputer_mock.get_info(),
+            "[1] Parent\n  [2] Child\n    [4] Grandchild\n"
+        );
+    }
+
+    #[test]
+    fn test_cmd_list_subsections() {
+        let task_formatter = &TaskFormatter {
+            supports_colors: false,
+            today: today_getter_fixed(),
+            tag_colors: Vec::new(),
+        };
+        let content = [
+            "# **Job**",
+            "## Backend",
+            "- [ ] Fix the API",
+            "### Database",
+            "- [ ] Migrate",
+            "## Frontend",
+            "- [x] Fix the CSS",
+            "# Perso",
+            "- [ ] Fix the roof",
+        ]
+        .join("\n");
+
+        let outputer_mock = &mut StringOutputerMock::new();
+        cmd(
+            outputer_mock,
+            &ContentGetterMock::new(Ok(content)),
+            task_formatter,
+            &ListOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            outputer_mock.get_info(),
+            [
+                "# **Job**",
+                "  # Backend",
+                "  [1] Fix the API",
+                "",
+                "    # Database",
+                "    [2] Migrate",
+                "",
+                "1 other open task outside of \"Job\".",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_cmd_list_tag_and_context() {
+        let task_formatter = &TaskFormatter {
+            supports_colors: false,
+            today: today_getter_fixed(),
+            tag_colors: Vec::new(),
+        };
+        let contents = [
+            "- [ ] Fix the build #backend",
+            "- [ ] Call the vendor #Backend @phone",
+            "- [ ] Call mom @phone",
+        ]
+        .join("\n");
+
+        let outputer_mock = &mut StringOutputerMock::new();
+        let content_getter_mock = &ContentGetterMock::new(Ok(contents.clone()));
+        cmd(
+            outputer_mock,
+            content_getter_mock,
+            task_formatter,
+            &ListOptions {
+                tag: Some(String::from("backend")),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            outputer_mock.get_info(),
+            "[1] Fix the build #backend\n[2] Call the vendor #Backend @phone\n"
+        );
+
+        let outputer_mock = &mut StringOutputerMock::new();
+        let content_getter_mock = &ContentGetterMock::new(Ok(contents));
+        cmd(
+            outputer_mock,
+            content_getter_mock,
+            task_formatter,
+            &ListOptions {
+                tag: Some(String::from("backend")),
+                context: Some(String::from("phone")),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            outputer_mock.get_info(),
+            "[2] Call the vendor #Backend @phone\n"
+        );
+    }
+
+    #[test]
+    fn test_cmd_list_sort_priority() {
+        let task_formatter = &TaskFormatter {
+            supports_colors: false,
+            today: today_getter_fixed(),
+            tag_colors: Vec::new(),
+        };
+        let outputer_mock = &mut StringOutputerMock::new();
+        let content_getter_mock = &ContentGetterMock::new(Ok([
+            "# Job",
+            "- [ ] No priority",
+            "- [ ] (B) Medium",
+            "  - [ ] (A) High subtask",
+            "# Perso",
+            "- [ ] Low !",
+            "- [ ] (A) High",
+        ]
+        .join("\n")));
+
+        cmd(
+            outputer_mock,
+            content_getter_mock,
+            task_formatter,
+            &ListOptions {
+                sort: Some(ListSort::Priority),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            outputer_mock.get_info(),
+            "# Job\n[3] (A) High subtask\n[2] (B) Medium\n[1] No priority\n\n# Perso\n[5] (A) High\n[4] Low !\n"
+        );
+    }
+
+    #[test]
+    fn test_cmd_list_states() {
+        let task_formatter = &TaskFormatter {
+            supports_colors: false,
+            today: today_getter_fixed(),
+            tag_colors: Vec::new(),
+        };
+        let outputer_mock = &mut StringOutputerMock::new();
+        let content_getter_mock = &ContentGetterMock::new(Ok([
+            "- [ ] Open",
+            "- [x] Done",
+            "- [X] Also done",
+            "- [-] Cancelled",
+            "- [/] In progress",
+            "- [>] Deferred",
+        ]
+        .join("\n")));
+
+        cmd(
+            outputer_mock,
+            content_getter_mock,
+            task_formatter,
+            &ListOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            outputer_mock.get_info(),
+            "[1] Open\n[5] In progress (in progress)\n[6] Deferred (deferred)\n"
+        );
+    }
+
+    #[test]
+    fn test_cmd_list_long() {
+        let task_formatter = &TaskFormatter {
+            supports_colors: false,
+            today: today_getter_fixed(),
+            tag_colors: Vec::new(),
+        };
+        let outputer_mock = &mut StringOutputerMock::new();
+        let content_getter_mock = &ContentGetterMock::new(Ok([
+            "- [ ] Fix the roof created:2020-01-10",
+            "- [ ] Call mom",
+        ]
+        .join("\n")));
+
+        cmd(
+            outputer_mock,
+            content_getter_mock,
+            task_formatter,
+            &ListOptions {
+                long: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            outputer_mock.get_info(),
+            "[1] Fix the roof created:2020-01-10 (5 months old)\n[2] Call mom\n"
+        );
+    }
 }
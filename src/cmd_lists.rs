@@ -0,0 +1,92 @@
+use crate::services::{ContentGetter, StringOutputer};
+use crate::tasks::get_open_tasks;
+
+// The lists of the lists file, with their number of open tasks; the list in
+// use is starred
+pub fn cmd(
+    outputer: &mut dyn StringOutputer,
+    lists: &[(String, String, &dyn ContentGetter)],
+    lists_path: &str,
+    taxfile_path: &str,
+) -> Result<(), String> {
+    if lists.is_empty() {
+        outputer.info(&format!("No list defined in {}", lists_path));
+        return Ok(());
+    }
+
+    let width = lists
+        .iter()
+        .map(|(name, _, _)| name.len())
+        .max()
+        .unwrap_or(0);
+
+    for (name, path, content_getter) in lists {
+        let count = match get_open_tasks(*content_getter) {
+            Ok((tasks, _, _, _)) => format!(
+                "{} open task{}",
+                tasks.len(),
+                if tasks.len() == 1 { "" } else { "s" }
+            ),
+            Err(_) => String::from("unreadable"),
+        };
+
+        outputer.info(&format!(
+            "{} {:width$}  {}  {}",
+            if path == taxfile_path { "*" } else { " " },
+            name,
+            path,
+            count,
+            width = width
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test::{ContentGetterMock, StringOutputerMock};
+
+    #[test]
+    fn test_cmd_lists() {
+        let work = ContentGetterMock::new(Ok(String::from(
+            "- [ ] Send the invoice\n- [x] Fix the build\n- [ ] Call the bank",
+        )));
+        let home = ContentGetterMock::new(Ok(String::from("- [ ] Fix the roof")));
+        let done = ContentGetterMock::new(Ok(String::from("- [x] Paint the fence")));
+        let old = ContentGetterMock::new(Err(String::from("Could not open file")));
+
+        let outputer_mock = &mut StringOutputerMock::new();
+        cmd(
+            outputer_mock,
+            &[
+                (String::from("work"), String::from("/work.md"), &work),
+                (String::from("home"), String::from("/home.md"), &home),
+                (String::from("done"), String::from("/done.md"), &done),
+                (String::from("archives"), String::from("/old.md"), &old),
+            ],
+            "/config/tax/lists.yml",
+            "/home.md",
+        )
+        .unwrap();
+        assert_eq!(
+            outputer_mock.get_info(),
+            [
+                "  work      /work.md  2 open tasks",
+                "* home      /home.md  1 open task",
+                "  done      /done.md  0 open tasks",
+                "  archives  /old.md  unreadable",
+                "",
+            ]
+            .join("\n")
+        );
+
+        let outputer_mock = &mut StringOutputerMock::new();
+        cmd(outputer_mock, &[], "/config/tax/lists.yml", "/home.md").unwrap();
+        assert_eq!(
+            outputer_mock.get_info(),
+            "No list defined in /config/tax/lists.yml\n"
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use crate::model::FileFormat;
+use crate::services::{ContentGetter, ContentSetter, StringOutputer, TaskFormatter, UserCmdRunner};
+use crate::tasks::{
+    filter_tasks_in_section_only, get_all_tasks, get_task_descendants, get_task_line_nums,
+    search_section, text_add_line_in_str, text_remove_lines_in_str,
+};
+
+pub fn cmd(
+    outputer: &mut dyn StringOutputer,
+    content_getter: &dyn ContentGetter,
+    content_setter: &mut dyn ContentSetter,
+    user_cmd_runner: &dyn UserCmdRunner,
+    task_formatter: &TaskFormatter,
+    rank_one_based: usize,
+    section_name: String,
+) -> Result<(), String> {
+    let (tasks, use_sections, sections, _) = get_all_tasks(content_getter)?;
+    if rank_one_based == 0 || rank_one_based > tasks.len() {
+        return Err(format!("Non existent task {}", rank_one_based));
+    }
+
+    let task = &tasks[rank_one_based - 1];
+
+    // the section of a todo.txt task is given by its +project tokens
+    if task.format == FileFormat::TodoTxt {
+        return Err(String::from(
+            "Cannot move a todo.txt task, change its +project instead",
+        ));
+    }
+
+    let section = match search_section(section_name.as_ref(), &sections) {
+        None => return Err(format!("Section not found: {}", section_name)),
+        Some(section) => section,
+    };
+
+    let content = content_getter.get_contents()?;
+    let lines: Vec<&str> = content
+        .strip_prefix('\u{feff}')
+        .unwrap_or(&content)
+        .lines()
+        .collect();
+
+    // the task moves along with its body and its subtasks
+    let descendants = get_task_descendants(&tasks, task);
+    let line_nums = get_task_line_nums(&tasks, task);
+    let headline_nums: Vec<usize> = descendants
+        .iter()
+        .map(|t| t.line_num)
+        .chain(std::iter::once(task.line_num))
+        .collect();
+
+    let moved_lines: Vec<String> = line_nums
+        .iter()
+        .map(|line_num| {
+            let line = lines[line_num - 1];
+            match task.org_level {
+                // an Org headline becomes a child of the section headline
+                Some(org_level) if headline_nums.contains(line_num) => {
+                    let stars = line.len() - line.trim_start_matches('*').len();
+                    format!(
+                        "{}{}",
+                        "*".repeat(stars + section.level + 1 - org_level),
+                        &line[stars..]
+                    )
+                }
+                Some(_) => line.to_string(),
+                None => line.strip_prefix(&task.indent).unwrap_or(line).to_string(),
+            }
+        })
+        .collect();
+
+    let section_tasks: Vec<_> = filter_tasks_in_section_only(&tasks, &section)
+        .into_iter()
+        .filter(|t| t.num != task.num && !descendants.iter().any(|d| d.num == t.num))
+        .collect();
+
+    // after the body of the last task of the section, like `tax add`
+    let (line_num, block) = match section_tasks.iter().map(|t| t.line_num_end).max() {
+        Some(line_num_end) => (line_num_end + 1, moved_lines.join("\n")),
+        None => (
+            section.line_num_end + 1,
+            format!("\n{}", moved_lines.join("\n")),
+        ),
+    };
+    let added_count = block.split('\n').count();
+
+    let added_content = text_add_line_in_str(&content, line_num, &block);
+    let moved_content = text_remove_lines_in_str(
+        &added_content,
+        line_nums
+            .into_iter()
+            .map(|n| if n >= line_num { n + added_count } else { n })
+            .collect(),
+    )?;
+
+    content_setter.set_contents(moved_content)?;
+
+    outputer.info(&format!(
+        "Moved to {}: {}",
+        section.plain_name,
+        task_formatter.display_numbered_task(task, use_sections, true)
+    ));
+
+    match user_cmd_runner.build(
+        "move",
+        "MOVE",
+        &format!("Moved \"{}\" to \"{}\"", task.name, section.plain_name),
+    ) {
+        Ok(Some(mut cmd)) => {
+            user_cmd_runner.run(user_cmd_runner.env_single_task(task, &mut cmd))?;
+        }
+        Ok(None) => (),
+        Err(e) => return Err(e),
+    };
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test::{
+        ContentGetterMock, ContentSetterMock, StringOutputerMock, UserCmdRunnerMock,
+    };
+
+    fn run(content_getter: &ContentGetterMock, rank: usize, section: &str) -> Option<String> {
+        let mut string_outputer = StringOutputerMock::new();
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        let user_cmd_runner = UserCmdRunnerMock::new();
+        let task_formatter = TaskFormatter::new(false);
+
+        cmd(
+            &mut string_outputer,
+            content_getter,
+            &mut content_setter,
+            &user_cmd_runner,
+            &task_formatter,
+            rank,
+            String::from(section),
+        )
+        .unwrap();
+
+        content_setter.content
+    }
+
+    #[test]
+    fn test_cmd_move_with_body() {
+        let content_getter = ContentGetterMock::new(Ok([
+            "# Inbox",
+            "- [ ] First",
+            "  Notes of first",
+            "  - [ ] Subtask",
+            "    Notes of subtask",
+            "  More notes of first",
+            "- [ ] Second",
+            "",
+            "# Someday",
+            "- [ ] Third",
+        ]
+        .join("\n")));
+
+        assert_eq!(
+            run(&content_getter, 1, "someday"),
+            Some(
+                [
+                    "# Inbox",
+                    "- [ ] Second",
+                    "",
+                    "# Someday",
+                    "- [ ] Third",
+                    "- [ ] First",
+                    "  Notes of first",
+                    "  - [ ] Subtask",
+                    "    Notes of subtask",
+                    "  More notes of first",
+                ]
+                .join("\n")
+            )
+        );
+    }
+
+    #[test]
+    fn test_cmd_move_subtask_to_empty_section() {
+        let content_getter = ContentGetterMock::new(Ok([
+            "# Inbox",
+            "- [ ] First",
+            "  - [ ] Subtask",
+            "    Notes of subtask",
+            "# Someday",
+            "# Later",
+        ]
+        .join("\n")));
+
+        assert_eq!(
+            run(&content_getter, 2, "someday"),
+            Some(
+                [
+                    "# Inbox",
+                    "- [ ] First",
+                    "# Someday",
+                    "",
+                    "- [ ] Subtask",
+                    "  Notes of subtask",
+                    "# Later",
+                ]
+                .join("\n")
+            )
+        );
+    }
+
+    #[test]
+    fn test_cmd_move_org() {
+        let content_getter = ContentGetterMock::new_org(Ok([
+            "* Inbox",
+            "** TODO First",
+            "*** TODO Subtask",
+            "* Work",
+            "** Backend",
+            "*** TODO Second",
+        ]
+        .join("\n")));
+
+        assert_eq!(
+            run(&content_getter, 1, "backend"),
+            Some(
+                [
+                    "* Inbox",
+                    "* Work",
+                    "** Backend",
+                    "*** TODO Second",
+                    "*** TODO First",
+                    "**** TODO Subtask",
+                ]
+                .join("\n")
+            )
+        );
+    }
+
+    #[test]
+    fn test_cmd_move_non_existent() {
+        let mut string_outputer = StringOutputerMock::new();
+        let content_getter = ContentGetterMock::new(Ok(String::from("# Inbox\n- [ ] First\n")));
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+
+        assert_eq!(
+            cmd(
+                &mut string_outputer,
+                &content_getter,
+                &mut content_setter,
+                &UserCmdRunnerMock::new(),
+                &TaskFormatter::new(false),
+                0,
+                String::from("inbox"),
+            ),
+            Err(String::from("Non existent task 0"))
+        );
+    }
+}
@@ -1,5 +1,9 @@
+use crate::model::Task;
 use crate::services::{ContentGetter, ContentSetter, StringOutputer, TaskFormatter, UserCmdRunner};
-use crate::tasks::{get_closed_tasks, text_remove_lines_in_str};
+use crate::tasks::{
+    get_all_tasks, get_task_descendants, state_is_closed, text_add_line_in_str,
+    text_remove_lines_in_str,
+};
 
 pub fn cmd(
     outputer: &mut dyn StringOutputer,
@@ -7,16 +11,50 @@ pub fn cmd(
     content_setter: &mut dyn ContentSetter,
     user_cmd_runner: &dyn UserCmdRunner,
     task_formatter: &TaskFormatter,
+    archive: Option<(&dyn ContentGetter, &mut dyn ContentSetter)>,
 ) -> Result<(), String> {
-    let (tasks, use_sections, _, _) = get_closed_tasks(content_getter)?;
+    let (all_tasks, use_sections, _, _) = get_all_tasks(content_getter)?;
+
+    // a pruned task takes its whole subtree along
+    let mut tasks: Vec<Task> = Vec::new();
+    for task in all_tasks.iter().filter(|t| state_is_closed(t.state)) {
+        if tasks.iter().any(|t| t.num == task.num) {
+            continue;
+        }
+
+        tasks.push(task.clone());
+        tasks.append(&mut get_task_descendants(&all_tasks, task));
+    }
 
     if tasks.is_empty() {
         outputer.info("No task to prune");
         return Ok(());
     }
 
-    let line_nums: Vec<usize> = (&tasks).iter().map(|t| t.line_num).collect();
-    let pruned_content = text_remove_lines_in_str(&content_getter.get_contents()?, line_nums)?;
+    let mut line_nums: Vec<usize> = tasks
+        .iter()
+        .flat_map(|t| t.line_num..=t.line_num_end)
+        .collect();
+    // the notes of a task may follow its subtasks
+    line_nums.sort_unstable();
+    line_nums.dedup();
+    let content = content_getter.get_contents()?;
+
+    // the archive is written first: a failure leaves the tasks in the task file
+    if let Some((archive_getter, archive_setter)) = archive {
+        let lines: Vec<&str> = content.trim_start_matches('\u{feff}').lines().collect();
+        let pruned_lines: Vec<&str> = line_nums
+            .iter()
+            .filter_map(|line_num| lines.get(*line_num - 1).copied())
+            .collect();
+        archive_setter.set_contents(text_add_line_in_str(
+            &archive_getter.get_contents()?,
+            usize::MAX,
+            &pruned_lines.join("\n"),
+        ))?;
+    }
+
+    let pruned_content = text_remove_lines_in_str(&content, line_nums)?;
 
     content_setter.set_contents(pruned_content)?;
 
@@ -42,3 +80,42 @@ pub fn cmd(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test::{
+        ContentGetterMock, ContentSetterMock, StringOutputerMock, UserCmdRunnerMock,
+    };
+
+    #[test]
+    fn test_cmd_prune_archive() {
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        let mut archive_setter = ContentSetterMock::new(Ok(()));
+
+        cmd(
+            &mut StringOutputerMock::new(),
+            &ContentGetterMock::new(Ok(String::from(
+                "# Job\n- [x] Send the invoice\n  - [ ] Print it\n- [ ] Call the bank\n",
+            ))),
+            &mut content_setter,
+            &UserCmdRunnerMock::new(),
+            &TaskFormatter::new(false),
+            Some((
+                &ContentGetterMock::new(Ok(String::from("- [x] Fix the roof\n"))),
+                &mut archive_setter,
+            )),
+        )
+        .unwrap();
+        assert_eq!(
+            archive_setter.content,
+            Some(String::from(
+                "- [x] Fix the roof\n- [x] Send the invoice\n  - [ ] Print it\n"
+            ))
+        );
+        assert_eq!(
+            content_setter.content,
+            Some(String::from("# Job\n- [ ] Call the bank\n"))
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use crate::model::Task;
+use crate::services::{ContentGetter, StringOutputer, TaskFormatter};
+use crate::tasks::{get_all_tasks, get_interval_minutes, get_time_intervals, text_format_duration};
+use chrono::{NaiveDate, NaiveDateTime};
+
+// Prints the time tracked per task and per section since the given date,
+// along with the estimates of the tasks
+pub fn cmd(
+    outputer: &mut dyn StringOutputer,
+    content_getter: &dyn ContentGetter,
+    timelog_getter: &dyn ContentGetter,
+    task_formatter: &TaskFormatter,
+    now: NaiveDateTime,
+    since: Option<NaiveDate>,
+) -> Result<(), String> {
+    let (tasks, use_sections, _, _) = get_all_tasks(content_getter)?;
+
+    // (task id, tracked minutes), in order of first tracking
+    let mut tracked: Vec<(String, i64)> = Vec::new();
+    for interval in get_time_intervals(timelog_getter)? {
+        if since.is_some_and(|since| interval.start.date() < since) {
+            continue;
+        }
+
+        let minutes = get_interval_minutes(&interval, now);
+        match tracked.iter_mut().find(|(id, _)| *id == interval.task_id) {
+            Some((_, total)) => *total += minutes,
+            None => tracked.push((interval.task_id.clone(), minutes)),
+        }
+    }
+
+    if tracked.is_empty() {
+        outputer.info("No tracked time");
+        return Ok(());
+    }
+
+    // tasks in file order, then the ids of the tasks no longer in the file
+    let report_tasks: Vec<(&Task, i64)> = tasks
+        .iter()
+        .filter_map(|task| {
+            tracked
+                .iter()
+                .find(|(id, _)| task.id.as_ref() == Some(id))
+                .map(|(_, minutes)| (task, *minutes))
+        })
+        .collect();
+
+    let removed_tasks: Vec<&(String, i64)> = tracked
+        .iter()
+        .filter(|(id, _)| !tasks.iter().any(|t| t.id.as_ref() == Some(id)))
+        .collect();
+
+    let mut section_num = 0;
+    for (index, (task, minutes)) in report_tasks.iter().enumerate() {
+        if use_sections {
+            if let Some(section) = &task.section {
+                if section.num != section_num {
+                    let section_tasks: Vec<(&Task, i64)> = report_tasks[index..]
+                        .iter()
+                        .take_while(|(t, _)| t.section.as_ref().map(|s| s.num) == Some(section.num))
+                        .cloned()
+                        .collect();
+
+                    outputer.info(&format!(
+                        "{}# {}: {}",
+                        if section_num == 0 { "" } else { "\n" },
+                        section.plain_name,
+                        format_time(
+                            section_tasks.iter().map(|(_, m)| m).sum(),
+                            sum_estimates(&section_tasks)
+                        )
+                    ));
+                    section_num = section.num;
+                }
+            }
+        }
+
+        outputer.info(&format!(
+            "{}: {}",
+            task_formatter.display_numbered_task(task, false, false),
+            format_time(*minutes, task.estimate)
+        ));
+    }
+
+    if !removed_tasks.is_empty() {
+        outputer.info("\n# Removed tasks");
+        for (id, minutes) in &removed_tasks {
+            outputer.info(&format!("^{}: {}", id, text_format_duration(*minutes)));
+        }
+    }
+
+    outputer.info(&format!(
+        "\nTotal: {}",
+        format_time(
+            tracked.iter().map(|(_, m)| m).sum(),
+            sum_estimates(&report_tasks)
+        )
+    ));
+
+    Ok(())
+}
+
+fn sum_estimates(tasks: &[(&Task, i64)]) -> Option<i64> {
+    tasks
+        .iter()
+        .filter_map(|(t, _)| t.estimate)
+        .fold(None, |sum, estimate| Some(sum.unwrap_or(0) + estimate))
+}
+
+// tracked time, with the estimate if any: 1h30m / 2h
+fn format_time(minutes: i64, estimate: Option<i64>) -> String {
+    match estimate {
+        Some(estimate) => format!(
+            "{} / {}",
+            text_format_duration(minutes),
+            text_format_duration(estimate)
+        ),
+        None => text_format_duration(minutes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test::{
+        now_getter_fixed, today_getter_fixed, ContentGetterMock, StringOutputerMock,
+    };
+
+    #[test]
+    fn test_cmd_report() {
+        let content_getter = ContentGetterMock::new(Ok([
+            "# Job",
+            "- [x] Write code ~2h ^ab12",
+            "- [/] Review ~30m ^cd34",
+            "# Home",
+            "- [ ] Fix the roof ^ef56",
+        ]
+        .join("\n")));
+        let timelog_getter = ContentGetterMock::new(Ok([
+            "ab12 2020-06-12T10:00:00 2020-06-12T11:00:00",
+            "ab12 2020-06-15T07:00:00 2020-06-15T08:30:00",
+            "gh78 2020-06-15T08:30:00 2020-06-15T08:40:00",
+            "ef56 2020-06-15T08:40:00 2020-06-15T09:00:00",
+            "cd34 2020-06-15T09:00:00",
+        ]
+        .join("\n")));
+
+        let mut outputer = StringOutputerMock::new();
+        cmd(
+            &mut outputer,
+            &content_getter,
+            &timelog_getter,
+            &TaskFormatter::new(false),
+            now_getter_fixed(),
+            Some(today_getter_fixed()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            outputer.get_info(),
+            [
+                "# Job: 2h / 2h30m",
+                "[1] Write code ~2h ^ab12: 1h30m / 2h",
+                "[2] Review ~30m ^cd34: 30m / 30m",
+                "",
+                "# Home: 20m",
+                "[3] Fix the roof ^ef56: 20m",
+                "",
+                "# Removed tasks",
+                "^gh78: 10m",
+                "",
+                "Total: 2h30m / 2h30m",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use crate::services::{ContentGetter, StringOutputer, TaskFormatter};
+use crate::tasks::{get_all_tasks, text_get_body};
+
+pub fn cmd(
+    outputer: &mut dyn StringOutputer,
+    content_getter: &dyn ContentGetter,
+    task_formatter: &TaskFormatter,
+    rank_one_based: usize,
+) -> Result<(), String> {
+    let (tasks, use_sections, _, _) = get_all_tasks(content_getter)?;
+    if rank_one_based == 0 || rank_one_based > tasks.len() {
+        return Err(format!("Non existent task {}", rank_one_based));
+    }
+
+    let task = &tasks[rank_one_based - 1];
+
+    outputer.info(&format!(
+        "{}{}",
+        task_formatter.display_numbered_task(task, use_sections, true),
+        match &task.comment {
+            Some(comment) => format!(" // {}", comment),
+            None => String::from(""),
+        }
+    ));
+
+    let body = text_get_body(task);
+    if !body.is_empty() {
+        outputer.info("");
+        for line in body {
+            outputer.info(&line);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test::{ContentGetterMock, StringOutputerMock};
+
+    #[test]
+    fn test_cmd_show() {
+        let task_formatter = &TaskFormatter::new(false);
+        let outputer_mock = &mut StringOutputerMock::new();
+        let content_getter_mock = &ContentGetterMock::new(Ok([
+            "- [ ] First",
+            "- [ ] Second // with comment",
+            "  Some notes",
+            "",
+            "  ```sh",
+            "    indented code",
+            "  ```",
+            "",
+            "- [ ] Third",
+        ]
+        .join("\n")));
+
+        cmd(outputer_mock, content_getter_mock, task_formatter, 2).unwrap();
+        assert_eq!(
+            outputer_mock.get_info(),
+            "[2] Second // with comment\n\nSome notes\n\n```sh\n  indented code\n```\n"
+        );
+    }
+}
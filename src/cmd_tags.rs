@@ -0,0 +1,85 @@
+use crate::model::Task;
+use crate::services::{ContentGetter, StringOutputer};
+use crate::tasks::{get_all_tasks, state_is_closed};
+
+pub fn cmd(
+    outputer: &mut dyn StringOutputer,
+    content_getter: &dyn ContentGetter,
+) -> Result<(), String> {
+    let (tasks, _, _, _) = get_all_tasks(content_getter)?;
+
+    let mut counts = count_tasks_by_label(&tasks, "#", |t| &t.tags);
+    counts.append(&mut count_tasks_by_label(&tasks, "@", |t| &t.contexts));
+
+    let width = counts.iter().map(|(label, _, _)| label.len()).max().unwrap_or(0);
+
+    for (label, nb_open, nb_closed) in counts {
+        outputer.info(&format!(
+            "{:width$}  {} open, {} closed",
+            label,
+            nb_open,
+            nb_closed,
+            width = width
+        ));
+    }
+
+    Ok(())
+}
+
+// (label, open count, closed count), sorted by label; labels are case insensitive
+fn count_tasks_by_label(
+    tasks: &[Task],
+    prefix: &str,
+    get_labels: fn(&Task) -> &Vec<String>,
+) -> Vec<(String, usize, usize)> {
+    let mut counts: Vec<(String, usize, usize)> = Vec::new();
+
+    for task in tasks {
+        for label in get_labels(task) {
+            let label = format!("{}{}", prefix, label);
+            let pos = match counts
+                .iter()
+                .position(|(l, _, _)| l.eq_ignore_ascii_case(&label))
+            {
+                Some(pos) => pos,
+                None => {
+                    counts.push((label, 0, 0));
+                    counts.len() - 1
+                }
+            };
+
+            if state_is_closed(task.state) {
+                counts[pos].2 += 1;
+            } else {
+                counts[pos].1 += 1;
+            }
+        }
+    }
+
+    counts.sort_by_key(|(label, _, _)| label.to_lowercase());
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test::{ContentGetterMock, StringOutputerMock};
+
+    #[test]
+    fn test_cmd_tags() {
+        let outputer_mock = &mut StringOutputerMock::new();
+        let content_getter_mock = &ContentGetterMock::new(Ok([
+            "- [ ] Fix the build #backend",
+            "- [x] Call the vendor #Backend @phone",
+            "- [ ] Review the mockups #ui",
+            "- [ ] Call mom @phone",
+        ]
+        .join("\n")));
+
+        cmd(outputer_mock, content_getter_mock).unwrap();
+        assert_eq!(
+            outputer_mock.get_info(),
+            "#backend  1 open, 1 closed\n#ui       1 open, 0 closed\n@phone    1 open, 1 closed\n"
+        );
+    }
+}
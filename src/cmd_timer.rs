@@ -0,0 +1,308 @@
+use crate::model::{Task, TimeInterval};
+use crate::services::{ContentGetter, ContentSetter, StringOutputer, TaskFormatter};
+use crate::tasks::{
+    get_all_tasks, get_interval_minutes, get_new_task_id, get_time_intervals, task_to_line,
+    text_format_duration, text_replace_line_in_str, time_intervals_to_string,
+};
+use chrono::NaiveDateTime;
+
+// Starts tracking time on the task, stopping the running timer if any. The
+// time log refers to tasks by id, so the task is given one if needed.
+#[allow(clippy::too_many_arguments)]
+pub fn start(
+    outputer: &mut dyn StringOutputer,
+    content_getter: &dyn ContentGetter,
+    content_setter: &mut dyn ContentSetter,
+    timelog_getter: &dyn ContentGetter,
+    timelog_setter: &mut dyn ContentSetter,
+    task_formatter: &TaskFormatter,
+    now: NaiveDateTime,
+    id_seed: u64,
+    rank_one_based: usize,
+) -> Result<(), String> {
+    let task_id = set_task_id(content_getter, content_setter, id_seed, rank_one_based)?;
+    let (tasks, use_sections, _, _) = get_all_tasks(content_getter)?;
+
+    let mut intervals = get_time_intervals(timelog_getter)?;
+    if intervals
+        .iter()
+        .any(|i| i.end.is_none() && i.task_id == task_id)
+    {
+        return Ok(());
+    }
+
+    stop_running_intervals(
+        outputer,
+        &tasks,
+        &mut intervals,
+        task_formatter,
+        use_sections,
+        now,
+    );
+
+    intervals.push(TimeInterval {
+        task_id,
+        start: now,
+        end: None,
+    });
+
+    timelog_setter.set_contents(time_intervals_to_string(&intervals))
+}
+
+// Id of the task, given one if it has none: `tax start` sets it before the
+// hook of the state change, which gets it in $TAX_TASK_ID
+pub fn set_task_id(
+    content_getter: &dyn ContentGetter,
+    content_setter: &mut dyn ContentSetter,
+    id_seed: u64,
+    rank_one_based: usize,
+) -> Result<String, String> {
+    let (tasks, _, _, _) = get_all_tasks(content_getter)?;
+    if rank_one_based == 0 || rank_one_based > tasks.len() {
+        return Err(format!("Non existent task {}", rank_one_based));
+    }
+
+    let task = &tasks[rank_one_based - 1];
+    match &task.id {
+        Some(id) => Ok(id.clone()),
+        None => {
+            let id = get_new_task_id(&tasks, id_seed);
+            let mut updated_task = task.clone();
+            updated_task.plain_name = format!("{} ^{}", task.plain_name, id);
+            updated_task.line = task_to_line(&updated_task);
+            content_setter.set_contents(text_replace_line_in_str(
+                &content_getter.get_contents()?,
+                updated_task.line_num,
+                &updated_task.line,
+            ))?;
+            Ok(id)
+        }
+    }
+}
+
+pub fn stop(
+    outputer: &mut dyn StringOutputer,
+    content_getter: &dyn ContentGetter,
+    timelog_getter: &dyn ContentGetter,
+    timelog_setter: &mut dyn ContentSetter,
+    task_formatter: &TaskFormatter,
+    now: NaiveDateTime,
+) -> Result<(), String> {
+    let (tasks, use_sections, _, _) = get_all_tasks(content_getter)?;
+    let mut intervals = get_time_intervals(timelog_getter)?;
+
+    if !stop_running_intervals(
+        outputer,
+        &tasks,
+        &mut intervals,
+        task_formatter,
+        use_sections,
+        now,
+    ) {
+        outputer.info("No running timer");
+        return Ok(());
+    }
+
+    timelog_setter.set_contents(time_intervals_to_string(&intervals))
+}
+
+// Returns true if a running timer has been stopped
+fn stop_running_intervals(
+    outputer: &mut dyn StringOutputer,
+    tasks: &[Task],
+    intervals: &mut [TimeInterval],
+    task_formatter: &TaskFormatter,
+    use_sections: bool,
+    now: NaiveDateTime,
+) -> bool {
+    let mut stopped = false;
+
+    for interval in intervals.iter_mut().filter(|i| i.end.is_none()) {
+        interval.end = Some(now);
+        stopped = true;
+
+        let task_display = match tasks
+            .iter()
+            .find(|t| t.id.as_ref() == Some(&interval.task_id))
+        {
+            Some(task) => task_formatter.display_numbered_task(task, use_sections, true),
+            None => format!("^{}", interval.task_id),
+        };
+
+        outputer.info(&format!(
+            "Stopped: {} ({})",
+            task_display,
+            text_format_duration(get_interval_minutes(interval, now))
+        ));
+    }
+
+    stopped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test::{
+        now_getter_fixed, ContentGetterMock, ContentSetterMock, StringOutputerMock,
+    };
+
+    #[test]
+    fn test_cmd_timer_start() {
+        let mut string_outputer = StringOutputerMock::new();
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        let mut timelog_setter = ContentSetterMock::new(Ok(()));
+
+        start(
+            &mut string_outputer,
+            &ContentGetterMock::new(Ok(String::from("- [ ] Write code ^ab12\n- [/] Review"))),
+            &mut content_setter,
+            &ContentGetterMock::new(Ok(String::from(
+                "ab12 2020-06-15T08:00:00 2020-06-15T09:00:00\nab12 2020-06-15T09:10:00\n",
+            ))),
+            &mut timelog_setter,
+            &TaskFormatter::new(false),
+            now_getter_fixed(),
+            0,
+            2,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content_setter.content,
+            Some(String::from("- [ ] Write code ^ab12\n- [/] Review ^7iyc"))
+        );
+        assert_eq!(
+            timelog_setter.content,
+            Some(String::from(
+                "ab12 2020-06-15T08:00:00 2020-06-15T09:00:00\nab12 2020-06-15T09:10:00 2020-06-15T09:30:00\n7iyc 2020-06-15T09:30:00\n"
+            ))
+        );
+        assert_eq!(
+            string_outputer.get_info(),
+            "Stopped: [1] Write code ^ab12 (20m)\n"
+        );
+    }
+
+    #[test]
+    fn test_cmd_timer_start_non_existent() {
+        let mut string_outputer = StringOutputerMock::new();
+        let mut content_setter = ContentSetterMock::new(Ok(()));
+        let mut timelog_setter = ContentSetterMock::new(Ok(()));
+
+        assert_eq!(
+            start(
+                &mut string_outputer,
+                &ContentGetterMock::new(Ok(String::from("- [ ] Write code ^ab12"))),
+                &mut content_setter,
+                &ContentGetterMock::new(Ok(String::new())),
+                &mut timelog_setter,
+                &TaskFormatter::new(false),
+                now_getter_fixed(),
+                0,
+                0,
+            ),
+            Err(String::from("Non existent task 0"))
+        );
+        assert_eq!(timelog_setter.content, None);
+    }
+
+    #[test]
+    fn test_cmd_timer_start_hook_task_id() {
+        use crate::cmd_check;
+        use crate::model::TaskState;
+        use crate::services::{ContentHandlerReal, UserCmdRunner};
+        use crate::settings::Settings;
+        use std::cell::RefCell;
+        use std::process::Command;
+        use std::rc::Rc;
+
+        // records the id of the task given to the hook
+        struct UserCmdRunnerRecorder {
+            task_ids: RefCell<Vec<Option<String>>>,
+        }
+
+        impl UserCmdRunner for UserCmdRunnerRecorder {
+            fn env_single_task<'a>(&self, task: &Task, cmd: &'a mut Command) -> &'a mut Command {
+                self.task_ids.borrow_mut().push(task.id.clone());
+                cmd
+            }
+
+            fn build(&self, _: &str, _: &str, _: &str) -> Result<Option<Command>, String> {
+                Ok(Some(Command::new("true")))
+            }
+
+            fn run(&self, _: &mut Command) -> Result<(), String> {
+                Ok(())
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!("tax-test-start-{}.md", std::process::id()));
+        std::fs::write(&path, "- [ ] Write code\n").unwrap();
+        let read_hash = Rc::default();
+        let content_handler_ref = &ContentHandlerReal {
+            path: String::from(path.to_str().unwrap()),
+            read_hash: Rc::clone(&read_hash),
+        };
+        let content_handler_mutref = &mut ContentHandlerReal {
+            path: String::from(path.to_str().unwrap()),
+            read_hash,
+        };
+        let user_cmd_runner = UserCmdRunnerRecorder {
+            task_ids: RefCell::new(Vec::new()),
+        };
+
+        // like `tax start`
+        set_task_id(content_handler_ref, content_handler_mutref, 0, 1).unwrap();
+        cmd_check::cmd(
+            &mut StringOutputerMock::new(),
+            content_handler_ref,
+            content_handler_mutref,
+            &user_cmd_runner,
+            &TaskFormatter::new(false),
+            &Settings::default(),
+            now_getter_fixed(),
+            0,
+            1,
+            TaskState::InProgress,
+        )
+        .unwrap();
+
+        assert_eq!(
+            user_cmd_runner.task_ids.into_inner(),
+            vec![Some(String::from("7iyc"))]
+        );
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "- [/] Write code ^7iyc\n"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_cmd_timer_stop() {
+        let mut string_outputer = StringOutputerMock::new();
+        let mut timelog_setter = ContentSetterMock::new(Ok(()));
+
+        stop(
+            &mut string_outputer,
+            &ContentGetterMock::new(Ok(String::from("- [/] Write code ^ab12"))),
+            &ContentGetterMock::new(Ok(String::from("ab12 2020-06-15T08:00:00\n"))),
+            &mut timelog_setter,
+            &TaskFormatter::new(false),
+            now_getter_fixed(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            timelog_setter.content,
+            Some(String::from(
+                "ab12 2020-06-15T08:00:00 2020-06-15T09:30:00\n"
+            ))
+        );
+        assert_eq!(
+            string_outputer.get_info(),
+            "Stopped: [1] Write code ^ab12 (in progress) (1h30m)\n"
+        );
+    }
+}
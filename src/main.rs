@@ -1,16 +1,25 @@
 #[macro_use]
 extern crate lazy_static;
 
-use clap::{crate_version, value_t, App, Arg, ArgMatches};
+use clap::{crate_version, App, Arg, ArgMatches};
 use colored::control::SHOULD_COLORIZE;
 
 mod services;
 use services::{
-    env_getter_real, home_getter_real, ContentHandlerReal, StringOutputerReal, TaskFormatter,
-    TaxfilePathGetter, TaxfilePathGetterReal, UserCmdRunnerReal,
+    config_dir_getter_real, env_getter_real, home_getter_real, id_seed_getter_real,
+    now_getter_real, ContentGetter, ContentHandlerReal, FileLockReal, OptionalFileHandlerReal,
+    StringOutputerReal, TaskFormatter, TaxfilePathGetter, TaxfilePathGetterReal, UserCmdRunnerReal,
 };
 
 mod model;
+use model::TaskState;
+use settings::Stamp;
+use std::env;
+use std::path::PathBuf;
+use std::rc::Rc;
+use tasks::{get_task_num, text_format_stamp, text_parse_since};
+
+mod settings;
 mod tasks;
 mod test_helpers;
 
@@ -18,11 +27,20 @@ mod cmd_add;
 mod cmd_cat;
 mod cmd_check;
 mod cmd_current;
+mod cmd_delete;
+mod cmd_due;
 mod cmd_edit;
 mod cmd_focus;
 mod cmd_focus_section;
+mod cmd_graph;
 mod cmd_list;
+mod cmd_lists;
+mod cmd_move;
 mod cmd_prune;
+mod cmd_report;
+mod cmd_show;
+mod cmd_tags;
+mod cmd_timer;
 mod cmd_which;
 
 fn main() -> Result<(), String> {
@@ -39,14 +57,38 @@ fn get_arg_matches() -> ArgMatches<'static> {
                 .long("all")
                 .help("Print all open tasks regardless of section focus"),
         )
+        .arg(
+            Arg::with_name("file")
+                .short("f")
+                .long("file")
+                .takes_value(true)
+                .value_name("PATH")
+                .conflicts_with("list")
+                .help("Use the given task file instead of $TAX_FILE"),
+        )
+        .arg(
+            Arg::with_name("list")
+                .short("l")
+                .long("list")
+                .takes_value(true)
+                .value_name("NAME")
+                .help("Use the task file of the given list of the lists file"),
+        )
         .subcommand(App::new("edit").about("Edit the current task list in $EDITOR"))
         .subcommand(
-            App::new("focus").about("Focus the given task").arg(
-                Arg::with_name("task-index")
-                    .index(1)
-                    .required(true)
-                    .help("Number of the task to focus"),
-            ),
+            App::new("focus")
+                .about("Focus the given task, or section")
+                .arg(
+                    Arg::with_name("task-index")
+                        .index(1)
+                        .required(true)
+                        .help("Number or ^id of the task to focus, or name of the section"),
+                )
+                .arg(
+                    Arg::with_name("only")
+                        .long("only")
+                        .help("Blur the other focused sections"),
+                ),
         )
         .subcommand(
             App::new("blur")
@@ -56,7 +98,7 @@ fn get_arg_matches() -> ArgMatches<'static> {
                     Arg::with_name("task-index")
                         .index(1)
                         .required(true)
-                        .help("Number of the task to blur"),
+                        .help("Number or ^id of the task to blur"),
                 ),
         )
         .subcommand(
@@ -66,7 +108,7 @@ fn get_arg_matches() -> ArgMatches<'static> {
                     Arg::with_name("task-index")
                         .index(1)
                         .required(true)
-                        .help("Number of the task to mark as completed"),
+                        .help("Number or ^id of the task to mark as completed"),
                 ),
         )
         .subcommand(
@@ -76,7 +118,75 @@ fn get_arg_matches() -> ArgMatches<'static> {
                     Arg::with_name("task-index")
                         .index(1)
                         .required(true)
-                        .help("Number of the task to mark as not completed"),
+                        .help("Number or ^id of the task to mark as not completed"),
+                ),
+        )
+        .subcommand(
+            App::new("start")
+                .about("Mark the given task as in progress")
+                .arg(
+                    Arg::with_name("task-index")
+                        .index(1)
+                        .required(true)
+                        .help("Number or ^id of the task to mark as in progress"),
+                ),
+        )
+        .subcommand(
+            App::new("cancel")
+                .about("Mark the given task as cancelled")
+                .arg(
+                    Arg::with_name("task-index")
+                        .index(1)
+                        .required(true)
+                        .help("Number or ^id of the task to mark as cancelled"),
+                ),
+        )
+        .subcommand(
+            App::new("defer")
+                .about("Mark the given task as deferred")
+                .arg(
+                    Arg::with_name("task-index")
+                        .index(1)
+                        .required(true)
+                        .help("Number or ^id of the task to mark as deferred"),
+                ),
+        )
+        .subcommand(
+            App::new("show")
+                .about("Print the given task along with its notes")
+                .arg(
+                    Arg::with_name("task-index")
+                        .index(1)
+                        .required(true)
+                        .help("Number or ^id of the task to show"),
+                ),
+        )
+        .subcommand(
+            App::new("delete")
+                .alias("rm")
+                .about("Remove the given task, its notes and its subtasks from the task list")
+                .arg(
+                    Arg::with_name("task-index")
+                        .index(1)
+                        .required(true)
+                        .help("Number or ^id of the task to remove"),
+                ),
+        )
+        .subcommand(
+            App::new("move")
+                .alias("mv")
+                .about("Move the given task, its notes and its subtasks to the end of a section")
+                .arg(
+                    Arg::with_name("task-index")
+                        .index(1)
+                        .required(true)
+                        .help("Number or ^id of the task to move"),
+                )
+                .arg(
+                    Arg::with_name("section")
+                        .index(2)
+                        .required(true)
+                        .help("Name of the section to move the task to"),
                 ),
         )
         .subcommand(
@@ -88,11 +198,68 @@ fn get_arg_matches() -> ArgMatches<'static> {
                         .short("a")
                         .long("all")
                         .help("Print all open tasks regardless of section focus"),
+                )
+                .arg(
+                    Arg::with_name("tag")
+                        .short("t")
+                        .long("tag")
+                        .takes_value(true)
+                        .help("Print only the tasks having the given #tag"),
+                )
+                .arg(
+                    Arg::with_name("context")
+                        .short("c")
+                        .long("context")
+                        .takes_value(true)
+                        .help("Print only the tasks having the given @context"),
+                )
+                .arg(
+                    Arg::with_name("sort")
+                        .long("sort")
+                        .takes_value(true)
+                        .possible_values(&["priority", "due"])
+                        .help("Sort the tasks of each section"),
+                )
+                .arg(
+                    Arg::with_name("long")
+                        .short("l")
+                        .long("long")
+                        .help("Print the age of the tasks having a creation date"),
                 ),
         )
+        .subcommand(App::new("tags").about("Print all tags and contexts with their task counts"))
+        .subcommand(App::new("stop").about("Stop tracking time on the started task"))
+        .subcommand(
+            App::new("report")
+                .about("Print reports on the task list")
+                .subcommand(
+                App::new("time")
+                    .about("Print the time tracked per task and per section, with estimates")
+                    .arg(
+                        Arg::with_name("since")
+                            .long("since")
+                            .takes_value(true)
+                            .help(
+                            "Count the time tracked since the given date (YYYY-MM-DD, monday...)",
+                        ),
+                    ),
+            ),
+        )
+        .subcommand(
+            App::new("graph").about("Print the task dependency graph in Graphviz DOT format"),
+        )
         .subcommand(
             App::new("current").about("Print the first open (focused if any) task of the list"),
         )
+        .subcommand(
+            App::new("due")
+                .about("Print the open tasks having a due date, soonest first")
+                .arg(
+                    Arg::with_name("check")
+                        .long("check")
+                        .help("Print overdue tasks only, and exit with an error if there is any"),
+                ),
+        )
         .subcommand(
             App::new("cycle")
                 .about("Like current, but changes task every minute if no task is focused"),
@@ -108,6 +275,9 @@ fn get_arg_matches() -> ArgMatches<'static> {
                 .about("Print the content of the task file without any processing"),
         )
         .subcommand(App::new("which").about("Print the path of the current task list file"))
+        .subcommand(
+            App::new("lists").about("Print the lists of the lists file, with their open tasks"),
+        )
         .subcommand(
             App::new("add")
                 .alias("push")
@@ -120,6 +290,11 @@ fn get_arg_matches() -> ArgMatches<'static> {
                         .takes_value(true)
                         .help("Section where to add task"),
                 )
+                .arg(
+                    Arg::with_name("stamp")
+                        .long("stamp")
+                        .help("Record the creation date of the task"),
+                )
                 .arg(
                     Arg::with_name("task-name")
                         .required(true)
@@ -137,6 +312,11 @@ fn get_arg_matches() -> ArgMatches<'static> {
                         .takes_value(true)
                         .help("Section where to add task"),
                 )
+                .arg(
+                    Arg::with_name("stamp")
+                        .long("stamp")
+                        .help("Record the creation date of the task"),
+                )
                 .arg(
                     Arg::with_name("task-name")
                         .required(true)
@@ -147,28 +327,117 @@ fn get_arg_matches() -> ArgMatches<'static> {
         .get_matches()
 }
 
+// --stamp records the creation date, unless stamping is already configured
+fn get_created_stamp(settings: &settings::Settings, stamp_flag: bool) -> Stamp {
+    match settings.stamp_created {
+        Stamp::None if stamp_flag => Stamp::Date,
+        stamp => stamp,
+    }
+}
+
+fn command_changes_files(matches: &ArgMatches) -> bool {
+    matches!(
+        matches.subcommand_name().unwrap_or_default(),
+        "focus"
+            | "blur"
+            | "check"
+            | "uncheck"
+            | "start"
+            | "stop"
+            | "cancel"
+            | "defer"
+            | "delete"
+            | "move"
+            | "prune"
+            | "add"
+            | "append"
+    )
+}
+
 fn run_app(matches: ArgMatches) -> Result<(), String> {
     let taxfile_path_getter = &TaxfilePathGetterReal {
         get_env: env_getter_real,
         get_home: home_getter_real,
+        get_config_dir: config_dir_getter_real,
+        // absolute, like the path given to the hooks
+        file: match matches.value_of("file") {
+            Some(file) => match env::current_dir() {
+                Ok(dir) => Some(String::from(dir.join(file).to_str().unwrap())),
+                Err(e) => return Err(format!("{}", e)),
+            },
+            None => None,
+        },
+        list: matches.value_of("list").map(String::from),
+    };
+
+    let file_path = taxfile_path_getter.get_taxfile_path()?;
+
+    // commands changing the files wait for the other ones to complete, unless
+    // run by a hook of a command holding the lock
+    let lock_path = taxfile_path_getter.get_lock_path()?;
+    let lock = if command_changes_files(&matches)
+        && env_getter_real("TAX_LOCK_HELD").as_ref() != Some(&lock_path)
+    {
+        Some(FileLockReal::lock(&lock_path)?)
+    } else {
+        None
     };
 
     let user_cmd_runner = &UserCmdRunnerReal {
         taxfile_path_getter,
         get_env: env_getter_real,
+        held_lock_path: lock.as_ref().map(|_| lock_path.clone()),
     };
 
-    let file_path = taxfile_path_getter.get_taxfile_path()?;
+    let now = now_getter_real();
 
+    let read_hash = Rc::default();
     let content_handler_ref = &ContentHandlerReal {
         path: file_path.clone(),
+        read_hash: Rc::clone(&read_hash),
+    };
+    let content_handler_mutref = &mut ContentHandlerReal {
+        path: file_path,
+        read_hash,
+    };
+
+    // the front matter of the task file overrides the environment; edit, cat
+    // and which use no setting, and still work to fix an invalid one
+    let settings = &match matches.subcommand_name() {
+        Some("edit") | Some("cat") | Some("which") => settings::Settings::default(),
+        _ => settings::get_file_settings(
+            settings::get_settings(env_getter_real)?,
+            &content_handler_ref.get_contents().unwrap_or_default(),
+        )?,
     };
-    let content_handler_mutref = &mut ContentHandlerReal { path: file_path };
+
+    let timelog_path = taxfile_path_getter.get_timelog_path()?;
+    let timelog_handler_ref = &OptionalFileHandlerReal {
+        path: timelog_path.clone(),
+    };
+    let timelog_handler_mutref = &mut OptionalFileHandlerReal { path: timelog_path };
+
+    // archive of the pruned tasks, next to the task file unless absolute
+    let archive_path = match &settings.archive {
+        Some(archive) => Some(String::from(
+            PathBuf::from(taxfile_path_getter.get_taxfile_dir()?)
+                .join(archive)
+                .to_str()
+                .unwrap(),
+        )),
+        None => None,
+    };
+    let archive_handler_ref = &archive_path
+        .clone()
+        .map(|path| OptionalFileHandlerReal { path });
+    let archive_handler_mutref = &mut archive_path.map(|path| OptionalFileHandlerReal { path });
 
     let outputer = &mut StringOutputerReal {};
 
     let task_formatter = &TaskFormatter {
         supports_colors: SHOULD_COLORIZE.should_colorize(),
+        today: now.date(),
+        tag_colors: settings.tag_colors.clone(),
     };
 
     let all = matches.is_present("all");
@@ -180,12 +449,20 @@ fn run_app(matches: ArgMatches) -> Result<(), String> {
     }
 
     match matches.subcommand() {
-        ("", None) => cmd_list::cmd(outputer, content_handler_ref, task_formatter, all),
+        ("", None) => cmd_list::cmd(
+            outputer,
+            content_handler_ref,
+            task_formatter,
+            &cmd_list::ListOptions {
+                all,
+                ..Default::default()
+            },
+        ),
         ("edit", _) => cmd_edit::cmd(taxfile_path_getter, user_cmd_runner),
         ("focus", Some(info)) => {
             let to_focus = info.value_of("task-index").unwrap();
 
-            match to_focus.parse::<usize>() {
+            match get_task_num(content_handler_ref, to_focus) {
                 Ok(rank_one_based) => cmd_focus::cmd(
                     outputer,
                     content_handler_ref,
@@ -202,13 +479,14 @@ fn run_app(matches: ArgMatches) -> Result<(), String> {
                     user_cmd_runner,
                     to_focus.to_string(),
                     true,
+                    info.is_present("only"),
                 ),
             }
         }
         ("blur", Some(info)) => {
             let to_focus = info.value_of("task-index").unwrap();
 
-            match to_focus.parse::<usize>() {
+            match get_task_num(content_handler_ref, to_focus) {
                 Ok(rank_one_based) => cmd_focus::cmd(
                     outputer,
                     content_handler_ref,
@@ -225,6 +503,7 @@ fn run_app(matches: ArgMatches) -> Result<(), String> {
                     user_cmd_runner,
                     to_focus.to_string(),
                     false,
+                    false,
                 ),
             }
         }
@@ -235,8 +514,11 @@ fn run_app(matches: ArgMatches) -> Result<(), String> {
             content_handler_mutref,
             user_cmd_runner,
             task_formatter,
-            value_t!(info.value_of("task-index"), usize).unwrap(),
-            true,
+            settings,
+            now,
+            id_seed_getter_real(),
+            get_task_num(content_handler_ref, info.value_of("task-index").unwrap())?,
+            TaskState::Done,
         ),
         ("uncheck", Some(info)) => cmd_check::cmd(
             outputer,
@@ -244,18 +526,153 @@ fn run_app(matches: ArgMatches) -> Result<(), String> {
             content_handler_mutref,
             user_cmd_runner,
             task_formatter,
-            value_t!(info.value_of("task-index"), usize).unwrap(),
-            false,
+            settings,
+            now,
+            id_seed_getter_real(),
+            get_task_num(content_handler_ref, info.value_of("task-index").unwrap())?,
+            TaskState::Open,
+        ),
+        ("start", Some(info)) => {
+            let rank_one_based =
+                get_task_num(content_handler_ref, info.value_of("task-index").unwrap())?;
+
+            // the hook of the state change gets the id of the timer
+            cmd_timer::set_task_id(
+                content_handler_ref,
+                content_handler_mutref,
+                id_seed_getter_real(),
+                rank_one_based,
+            )?;
+
+            cmd_check::cmd(
+                outputer,
+                content_handler_ref,
+                content_handler_mutref,
+                user_cmd_runner,
+                task_formatter,
+                settings,
+                now,
+                id_seed_getter_real(),
+                rank_one_based,
+                TaskState::InProgress,
+            )?;
+
+            cmd_timer::start(
+                outputer,
+                content_handler_ref,
+                content_handler_mutref,
+                timelog_handler_ref,
+                timelog_handler_mutref,
+                task_formatter,
+                now,
+                id_seed_getter_real(),
+                rank_one_based,
+            )
+        }
+        ("stop", _) => cmd_timer::stop(
+            outputer,
+            content_handler_ref,
+            timelog_handler_ref,
+            timelog_handler_mutref,
+            task_formatter,
+            now,
+        ),
+        ("report", Some(info)) => match info.subcommand() {
+            ("time", Some(report_info)) => cmd_report::cmd(
+                outputer,
+                content_handler_ref,
+                timelog_handler_ref,
+                task_formatter,
+                now,
+                match report_info.value_of("since") {
+                    Some(since) => Some(text_parse_since(since, now.date())?),
+                    None => None,
+                },
+            ),
+            _ => Err(String::from("Unknown report; available reports: time")),
+        },
+        ("cancel", Some(info)) => cmd_check::cmd(
+            outputer,
+            content_handler_ref,
+            content_handler_mutref,
+            user_cmd_runner,
+            task_formatter,
+            settings,
+            now,
+            id_seed_getter_real(),
+            get_task_num(content_handler_ref, info.value_of("task-index").unwrap())?,
+            TaskState::Cancelled,
+        ),
+        ("defer", Some(info)) => cmd_check::cmd(
+            outputer,
+            content_handler_ref,
+            content_handler_mutref,
+            user_cmd_runner,
+            task_formatter,
+            settings,
+            now,
+            id_seed_getter_real(),
+            get_task_num(content_handler_ref, info.value_of("task-index").unwrap())?,
+            TaskState::Deferred,
+        ),
+
+        ("show", Some(info)) => cmd_show::cmd(
+            outputer,
+            content_handler_ref,
+            task_formatter,
+            get_task_num(content_handler_ref, info.value_of("task-index").unwrap())?,
+        ),
+        ("delete", Some(info)) => cmd_delete::cmd(
+            outputer,
+            content_handler_ref,
+            content_handler_mutref,
+            user_cmd_runner,
+            task_formatter,
+            get_task_num(content_handler_ref, info.value_of("task-index").unwrap())?,
+        ),
+        ("move", Some(info)) => cmd_move::cmd(
+            outputer,
+            content_handler_ref,
+            content_handler_mutref,
+            user_cmd_runner,
+            task_formatter,
+            get_task_num(content_handler_ref, info.value_of("task-index").unwrap())?,
+            info.value_of_lossy("section").unwrap().to_string(),
         ),
 
         ("list", Some(info)) => cmd_list::cmd(
             outputer,
             content_handler_ref,
             task_formatter,
-            info.is_present("all"),
+            &cmd_list::ListOptions {
+                all: info.is_present("all"),
+                tag: info.value_of_lossy("tag").map(|s| s.trim_start_matches('#').to_string()),
+                context: info
+                    .value_of_lossy("context")
+                    .map(|s| s.trim_start_matches('@').to_string()),
+                sort: match info.value_of("sort") {
+                    Some("priority") => Some(cmd_list::ListSort::Priority),
+                    Some("due") => Some(cmd_list::ListSort::Due),
+                    _ => None,
+                },
+                long: info.is_present("long"),
+            },
+        ),
+        ("tags", _) => cmd_tags::cmd(outputer, content_handler_ref),
+        ("graph", _) => cmd_graph::cmd(outputer, content_handler_ref),
+        ("current", _) => cmd_current::cmd(outputer, content_handler_ref, task_formatter, None),
+        ("cycle", _) => cmd_current::cmd(
+            outputer,
+            content_handler_ref,
+            task_formatter,
+            Some(settings.cycle_minutes),
+        ),
+        ("due", Some(info)) => cmd_due::cmd(
+            outputer,
+            content_handler_ref,
+            task_formatter,
+            info.is_present("check"),
         ),
-        ("current", _) => cmd_current::cmd(outputer, content_handler_ref, task_formatter, false),
-        ("cycle", _) => cmd_current::cmd(outputer, content_handler_ref, task_formatter, true),
 
         ("prune", _) => cmd_prune::cmd(
             outputer,
@@ -263,12 +680,38 @@ fn run_app(matches: ArgMatches) -> Result<(), String> {
             content_handler_mutref,
             user_cmd_runner,
             task_formatter,
+            match (archive_handler_ref, archive_handler_mutref) {
+                (Some(archive_ref), Some(archive_mutref)) => Some((archive_ref, archive_mutref)),
+                _ => None,
+            },
         ),
 
         ("cat", _) => cmd_cat::cmd(outputer, content_handler_ref),
 
         ("which", _) => cmd_which::cmd(outputer, taxfile_path_getter),
 
+        ("lists", _) => {
+            let lists = taxfile_path_getter.get_lists()?;
+            let list_handlers: Vec<ContentHandlerReal> = lists
+                .iter()
+                .map(|(_, path)| ContentHandlerReal {
+                    path: path.clone(),
+                    read_hash: Rc::default(),
+                })
+                .collect();
+
+            cmd_lists::cmd(
+                outputer,
+                &lists
+                    .into_iter()
+                    .zip(list_handlers.iter())
+                    .map(|((name, path), handler)| (name, path, handler as &dyn ContentGetter))
+                    .collect::<Vec<_>>(),
+                &taxfile_path_getter.get_lists_path()?,
+                &taxfile_path_getter.get_taxfile_path()?,
+            )
+        }
+
         ("add", Some(info)) => cmd_add::cmd(
             outputer,
             content_handler_ref,
@@ -277,7 +720,10 @@ fn run_app(matches: ArgMatches) -> Result<(), String> {
             task_formatter,
             info.values_of_lossy("task-name").unwrap(),
             info.value_of_lossy("section").map(|s| s.to_string()),
+            settings.default_section.clone(),
             cmd_add::AddPosition::Prepend,
+            text_format_stamp(get_created_stamp(settings, info.is_present("stamp")), now),
+            id_seed_getter_real(),
         ),
 
         ("append", Some(info)) => cmd_add::cmd(
@@ -288,7 +734,10 @@ fn run_app(matches: ArgMatches) -> Result<(), String> {
             task_formatter,
             info.values_of_lossy("task-name").unwrap(),
             info.value_of_lossy("section").map(|s| s.to_string()),
+            settings.default_section.clone(),
             cmd_add::AddPosition::Append,
+            text_format_stamp(get_created_stamp(settings, info.is_present("stamp")), now),
+            id_seed_getter_real(),
         ),
         _ => Err("Unknown command".to_string()),
     }
@@ -1,16 +1,74 @@
+use chrono::{NaiveDate, NaiveDateTime};
 use std::rc::Rc;
 
-#[derive(std::clone::Clone, Debug, PartialEq)]
+#[derive(std::clone::Clone, Debug, Default, PartialEq)]
 pub struct Task {
     pub num: usize,
     pub name: String,
     pub plain_name: String,
     pub comment: Option<String>,
-    pub is_checked: bool,
+    pub state: TaskState,
     pub line_num: usize,
+    pub line_num_end: usize,
     pub line: String,
     pub is_focused: bool,
     pub section: Option<Rc<Section>>,
+    pub indent: String,
+    pub depth: usize,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub body: Vec<String>,
+    pub due: Option<NaiveDate>,
+    pub tags: Vec<String>,
+    pub contexts: Vec<String>,
+    pub priority: Option<char>,
+    pub recurrence: Option<Recurrence>,
+    pub done_date: Option<NaiveDate>,
+    pub created: Option<NaiveDate>,
+    pub id: Option<String>,
+    pub blocked_by: Vec<String>,
+    pub is_blocked: bool,
+    // in minutes
+    pub estimate: Option<i64>,
+    pub format: FileFormat,
+    // level of the headline, for Org tasks written as TODO headlines
+    pub org_level: Option<usize>,
+}
+
+#[derive(std::clone::Clone, Copy, Debug, Default, PartialEq)]
+pub enum FileFormat {
+    #[default]
+    Markdown,
+    Org,
+    TodoTxt,
+}
+
+#[derive(std::clone::Clone, Copy, Debug, Default, PartialEq)]
+pub enum TaskState {
+    #[default]
+    Open,
+    Done,
+    Cancelled,
+    InProgress,
+    Deferred,
+}
+
+#[derive(std::clone::Clone, Debug, PartialEq)]
+pub enum Recurrence {
+    Daily,
+    Weekdays,
+    Weekly,
+    Monthly,
+    Yearly,
+    MonthDay(u32),
+}
+
+// Time spent on the task having the given id; a running interval has no end
+#[derive(std::clone::Clone, Debug, PartialEq)]
+pub struct TimeInterval {
+    pub task_id: String,
+    pub start: NaiveDateTime,
+    pub end: Option<NaiveDateTime>,
 }
 
 #[derive(std::clone::Clone, Debug, PartialEq)]
@@ -23,4 +81,8 @@ pub struct Section {
     pub line_num_end: usize,
     pub line: String,
     pub level: usize,
+    pub format: FileFormat,
+    // enclosing section, for subsections
+    pub parent: Option<Rc<Section>>,
+    pub depth: usize,
 }
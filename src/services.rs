@@ -1,24 +1,45 @@
-use crate::model::Task;
+use crate::model::{FileFormat, Task, TaskState};
+use crate::settings::{get_file_settings, parse_lists, Settings};
+use crate::tasks::{
+    section_path, state_is_closed, text_file_marker, text_get_priority_markup, text_is_file_marker,
+};
 
+use chrono::{Local, NaiveDate, NaiveDateTime};
 use colored::*;
+use regex::Regex;
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
 use std::env;
 use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub struct TaskFormatter {
     pub supports_colors: bool,
+    pub today: NaiveDate,
+    // (tag, color name)
+    pub tag_colors: Vec<(String, String)>,
 }
 impl TaskFormatter {
     #[allow(dead_code)]
     pub fn new(supports_colors: bool) -> Self {
         TaskFormatter {
             supports_colors,
+            today: now_getter_real().date(),
+            tag_colors: Vec::new(),
         }
     }
 
-    pub fn display_numbered_task(&self, task: &Task, use_sections: bool, use_styles: bool) -> String {
+    pub fn display_numbered_task(
+        &self,
+        task: &Task,
+        use_sections: bool,
+        use_styles: bool,
+    ) -> String {
         format!(
             "{} {}{}",
             self.display_task_num(task),
@@ -27,9 +48,9 @@ impl TaskFormatter {
                     Some(rc) => format!(
                         "{} ~ ",
                         if use_styles && task.section.as_ref().unwrap().is_focused {
-                            self.display_bold(&rc.plain_name)
+                            self.display_bold(&section_path(rc))
                         } else {
-                            rc.plain_name.clone()
+                            section_path(rc)
                         }
                     ),
                     None => String::from(""),
@@ -57,11 +78,87 @@ impl TaskFormatter {
         }
     }
 
+    pub fn display_dimmed(&self, s: &str) -> String {
+        if self.supports_colors {
+            s.dimmed().to_string()
+        } else {
+            s.to_string()
+        }
+    }
+
     pub fn display_task_name(&self, task: &Task, use_styles: bool) -> String {
-        if use_styles && task.is_focused {
+        let name = if use_styles && task.is_focused {
             self.display_bold(&task.plain_name)
         } else {
             task.plain_name.clone()
+        };
+
+        let name = match (task.priority, text_get_priority_markup(&task.plain_name)) {
+            (Some(priority), Some(priority_markup)) if self.supports_colors => {
+                let styled_markup = match priority {
+                    'A' => priority_markup.red().bold(),
+                    'B' => priority_markup.yellow().bold(),
+                    _ => priority_markup.bold(),
+                };
+                name.replacen(&priority_markup, &styled_markup.to_string(), 1)
+            }
+            _ => name,
+        };
+
+        let name = match task.due {
+            Some(due) if self.supports_colors && !state_is_closed(task.state) => {
+                let due_markup = format!("due:{}", due.format("%Y-%m-%d"));
+                if due < self.today {
+                    name.replace(&due_markup, &due_markup.red().bold().to_string())
+                } else if due == self.today {
+                    name.replace(&due_markup, &due_markup.yellow().bold().to_string())
+                } else {
+                    name
+                }
+            }
+            _ => name,
+        };
+
+        let mut name = name;
+        if self.supports_colors {
+            for (tag, color) in &self.tag_colors {
+                if task.tags.contains(tag) {
+                    let tag_regex =
+                        Regex::new(&format!(r"(^|\s)(#{})\b", regex::escape(tag))).unwrap();
+                    name = tag_regex
+                        .replace_all(&name, |cap: &regex::Captures| {
+                            format!("{}{}", &cap[1], cap[2].color(color.as_str()))
+                        })
+                        .to_string();
+                }
+            }
+        }
+
+        let name = match &task.id {
+            Some(id) if self.supports_colors => {
+                let id_markup = format!("^{}", id);
+                name.replace(&id_markup, &id_markup.dimmed().to_string())
+            }
+            _ => name,
+        };
+
+        let mut labels: Vec<&str> = match task.state {
+            TaskState::Open | TaskState::Done => vec![],
+            TaskState::InProgress => vec!["(in progress)"],
+            TaskState::Cancelled => vec!["(cancelled)"],
+            TaskState::Deferred => vec!["(deferred)"],
+        };
+
+        if task.is_blocked && !state_is_closed(task.state) {
+            labels.push("(blocked)");
+        }
+
+        if !use_styles || labels.is_empty() {
+            name
+        } else if self.supports_colors {
+            format!("{} {}", name, labels.join(" ").dimmed())
+        } else {
+            format!("{} {}", name, labels.join(" "))
         }
     }
 
@@ -86,6 +183,7 @@ impl StringOutputer for StringOutputerReal {
 }
 
 pub type EnvGetter = fn(&str) -> Option<String>;
+#[allow(clippy::manual_ok_err)]
 pub fn env_getter_real(name: &str) -> Option<String> {
     match env::var(name) {
         Ok(v) => Some(v),
@@ -93,23 +191,63 @@ pub fn env_getter_real(name: &str) -> Option<String> {
     }
 }
 
+pub fn now_getter_real() -> NaiveDateTime {
+    Local::now().naive_local()
+}
+
 pub type HomeGetter = fn() -> Option<PathBuf>;
+// Seed of the ids of new tasks
+pub fn id_seed_getter_real() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
 pub fn home_getter_real() -> Option<PathBuf> {
     dirs::home_dir()
 }
 
+pub fn config_dir_getter_real() -> Option<PathBuf> {
+    dirs::config_dir()
+}
+
 pub trait TaxfilePathGetter {
     fn get_taxfile_path(&self) -> Result<String, String>;
     fn get_taxfile_dir(&self) -> Result<String, String>;
+    fn get_timelog_path(&self) -> Result<String, String>;
+    fn get_lock_path(&self) -> Result<String, String>;
+    fn get_lists_path(&self) -> Result<String, String>;
+    fn get_lists(&self) -> Result<Vec<(String, String)>, String>;
 }
 
 pub struct TaxfilePathGetterReal {
     pub get_env: EnvGetter,
     pub get_home: HomeGetter,
+    pub get_config_dir: HomeGetter,
+    // --file, taking precedence over the list and the environment
+    pub file: Option<String>,
+    // --list, the name of one of the lists of the lists file
+    pub list: Option<String>,
 }
 
 impl TaxfilePathGetter for TaxfilePathGetterReal {
     fn get_taxfile_path(&self) -> Result<String, String> {
+        if let Some(file) = &self.file {
+            return Ok(file.clone());
+        }
+
+        if let Some(list) = &self.list {
+            return match self.get_lists()?.into_iter().find(|(name, _)| name == list) {
+                Some((_, path)) => Ok(path),
+                None => Err(format!(
+                    "List not found: {} (lists are defined in {})",
+                    list,
+                    self.get_lists_path()?
+                )),
+            };
+        }
+
         match get_env_var_if_not_empty("TAX_FILE", self.get_env) {
             Some(v) => Ok(v),
             None => match (self.get_home)() {
@@ -122,33 +260,176 @@ impl TaxfilePathGetter for TaxfilePathGetterReal {
     }
 
     fn get_taxfile_dir(&self) -> Result<String, String> {
-        let taxfile = self.get_taxfile_path()?;
+        let taxfile = self.get_main_taxfile_path()?;
+        // the sidecar files of a directory of task files go inside it
+        if Path::new(&taxfile).is_dir() {
+            return Ok(taxfile);
+        }
         let mut taxfile_pathbuf = PathBuf::from(taxfile);
         taxfile_pathbuf.pop();
         Ok(String::from(taxfile_pathbuf.to_str().unwrap()))
     }
+
+    // Hidden sidecar file, next to the task file: ~/.tasks.md.timelog
+    fn get_timelog_path(&self) -> Result<String, String> {
+        self.get_sidecar_path("timelog")
+    }
+
+    // Hidden sidecar file, next to the task file: ~/.tasks.md.lock
+    fn get_lock_path(&self) -> Result<String, String> {
+        self.get_sidecar_path("lock")
+    }
+
+    // ~/.config/tax/lists.yml on Linux
+    fn get_lists_path(&self) -> Result<String, String> {
+        match (self.get_config_dir)() {
+            None => Err(String::from("Could not find config dir")),
+            Some(config_dir) => Ok(String::from(
+                config_dir.join("tax").join("lists.yml").to_str().unwrap(),
+            )),
+        }
+    }
+
+    // The named task lists, in the order of the lists file; the paths are
+    // relative to the home dir when starting with ~/, to the lists file
+    // otherwise
+    fn get_lists(&self) -> Result<Vec<(String, String)>, String> {
+        let lists_path = self.get_lists_path()?;
+        if !Path::new(&lists_path).exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = match fs::read_to_string(&lists_path) {
+            Ok(content) => content,
+            Err(_) => return Err(format!("Could not read file {}", &lists_path)),
+        };
+
+        let mut lists_dir = PathBuf::from(&lists_path);
+        lists_dir.pop();
+
+        let mut lists: Vec<(String, String)> = Vec::new();
+        for (name, path) in parse_lists(&content)? {
+            let path = match path.strip_prefix("~/") {
+                Some(home_path) => match (self.get_home)() {
+                    None => return Err(String::from("Could not find home dir")),
+                    Some(home) => home.join(home_path),
+                },
+                None => lists_dir.join(path),
+            };
+            lists.push((name, String::from(path.to_str().unwrap())));
+        }
+
+        Ok(lists)
+    }
+}
+
+impl TaxfilePathGetterReal {
+    // The first file of a colon-separated list of task files
+    fn get_main_taxfile_path(&self) -> Result<String, String> {
+        let taxfile = self.get_taxfile_path()?;
+        match env::split_paths(&taxfile).find(|p| !p.as_os_str().is_empty()) {
+            Some(main_path) => Ok(String::from(main_path.to_str().unwrap())),
+            None => Ok(taxfile),
+        }
+    }
+
+    fn get_sidecar_path(&self, extension: &str) -> Result<String, String> {
+        let taxfile = self.get_main_taxfile_path()?;
+        let file_name = match Path::new(&taxfile).file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => return Err(format!("Invalid task file path {}", taxfile)),
+        };
+
+        let sidecar_pathbuf =
+            PathBuf::from(self.get_taxfile_dir()?).join(format!(".{}.{}", file_name, extension));
+        Ok(String::from(sidecar_pathbuf.to_str().unwrap()))
+    }
 }
 
 pub struct ContentHandlerReal {
     pub path: String,
+    // hash of the contents first read by the command, shared by the handlers
+    // reading and writing the same file
+    pub read_hash: Rc<Cell<Option<u64>>>,
+}
+
+impl ContentHandlerReal {
+    // A command reads the file more than once, and finds tasks by their line
+    // number: the file must not have been changed by another program (like an
+    // editor) since its first read
+    fn check_unchanged(&self, content: &str) -> Result<(), String> {
+        let hash = text_hash(content);
+        match self.read_hash.get() {
+            Some(read_hash) if read_hash != hash => Err(format!(
+                "The file {} was changed while running the command; nothing was written, try again",
+                &self.path
+            )),
+            Some(_) => Ok(()),
+            None => {
+                self.read_hash.set(Some(hash));
+                Ok(())
+            }
+        }
+    }
+}
+
+fn text_hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
 }
 
 pub trait ContentGetter {
     fn get_contents(&self) -> Result<String, String>;
+
+    fn get_format(&self) -> FileFormat {
+        FileFormat::Markdown
+    }
 }
 
-impl ContentGetter for ContentHandlerReal {
-    fn get_contents(&self) -> Result<String, String> {
-        match File::open(&self.path) {
-            Err(_) => Err(format!("Could not open file {}", &self.path)),
-            Ok(mut f) => {
+impl ContentHandlerReal {
+    fn read_contents(&self) -> Result<String, String> {
+        let content = match get_aggregated_paths(&self.path)? {
+            None => read_file(&self.path)?,
+            Some(paths) => {
+                // each file follows its marker line, and ends with a newline
+                // but the last one, as it may be the case in its own file
                 let mut content = String::new();
-
-                match f.read_to_string(&mut content) {
-                    Err(_) => Err(format!("Could not read file {}", &self.path)),
-                    Ok(_) => Ok(content),
+                for (index, path) in paths.iter().enumerate() {
+                    let file_content = read_file(path)?;
+                    // a BOM would hide the first line of the file
+                    let file_content = file_content
+                        .strip_prefix('\u{feff}')
+                        .unwrap_or(&file_content);
+                    content.push_str(&text_file_marker(&get_file_name(path)));
+                    content.push('\n');
+                    content.push_str(file_content);
+                    if index + 1 < paths.len()
+                        && !file_content.is_empty()
+                        && !file_content.ends_with('\n')
+                    {
+                        content.push('\n');
+                    }
                 }
+                content
             }
+        };
+
+        Ok(content)
+    }
+}
+
+impl ContentGetter for ContentHandlerReal {
+    fn get_contents(&self) -> Result<String, String> {
+        let content = self.read_contents()?;
+        self.check_unchanged(&content)?;
+        Ok(content)
+    }
+
+    fn get_format(&self) -> FileFormat {
+        match get_aggregated_paths(&self.path) {
+            Ok(Some(paths)) => get_file_format(&paths[0]),
+            _ => get_file_format(&self.path),
         }
     }
 }
@@ -159,16 +440,288 @@ pub trait ContentSetter {
 
 impl ContentSetter for ContentHandlerReal {
     fn set_contents(&mut self, contents: String) -> Result<(), String> {
-        match fs::write(&self.path, contents) {
+        if self.read_hash.get().is_some() {
+            self.get_contents()?;
+        }
+
+        match get_aggregated_paths(&self.path)? {
+            None => {
+                if let Err(e) = write_file_atomically(&self.path, &contents) {
+                    return Err(format!("Unable to write file {}: {}", &self.path, e));
+                }
+            }
+            Some(paths) => set_aggregated_contents(&paths, &contents)?,
+        }
+
+        // the next reads are checked against what is now in the file, which
+        // is not always the written contents: the lines of an aggregated list
+        // are spread over its files, so the hash is of the contents read back
+        self.read_hash.set(Some(text_hash(&self.read_contents()?)));
+        Ok(())
+    }
+}
+
+fn read_file(path: &str) -> Result<String, String> {
+    match File::open(path) {
+        Err(_) => Err(format!("Could not open file {}", path)),
+        Ok(mut f) => {
+            let mut content = String::new();
+
+            match f.read_to_string(&mut content) {
+                Err(_) => Err(format!("Could not read file {}", path)),
+                Ok(_) => Ok(content),
+            }
+        }
+    }
+}
+
+fn get_file_name(path: &str) -> String {
+    match Path::new(path).file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => String::from(path),
+    }
+}
+
+// todo.txt files are known by their name, as other .txt files may be notes
+fn get_file_format(path: &str) -> FileFormat {
+    let name = get_file_name(path).to_lowercase();
+    match Path::new(path).extension() {
+        Some(ext) if ext.eq_ignore_ascii_case("org") => FileFormat::Org,
+        _ if name == "todo.txt" || name == "done.txt" => FileFormat::TodoTxt,
+        _ => FileFormat::Markdown,
+    }
+}
+
+// The files of an aggregated task list: the Markdown or Org files of a
+// directory, by name, or the files of a colon-separated list; None for a
+// single task file
+pub fn get_aggregated_paths(path: &str) -> Result<Option<Vec<String>>, String> {
+    let paths: Vec<String> = if Path::new(path).is_dir() {
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(_) => return Err(format!("Could not read directory {}", path)),
+        };
+
+        // hidden files are left out, like the sidecar files of tax
+        let mut paths: Vec<String> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && !get_file_name(p.to_str().unwrap()).starts_with('.'))
+            .filter(|p| match p.extension() {
+                Some(ext) => ["md", "markdown", "org"]
+                    .iter()
+                    .any(|e| ext.eq_ignore_ascii_case(e)),
+                None => false,
+            })
+            .map(|p| String::from(p.to_str().unwrap()))
+            .collect();
+        paths.sort();
+
+        // the archive of a file, once pruned, is not one of the task files
+        let archive_paths: Vec<PathBuf> = paths
+            .iter()
+            .filter_map(|p| {
+                let settings = get_file_settings(Settings::default(), &read_file(p).ok()?).ok()?;
+                Some(Path::new(path).join(settings.archive?))
+            })
+            .collect();
+        paths.retain(|p| !archive_paths.iter().any(|a| a == Path::new(p)));
+
+        if paths.is_empty() {
+            return Err(format!("No task file in directory {}", path));
+        }
+        paths
+    } else {
+        let paths: Vec<String> = env::split_paths(path)
+            .filter(|p| !p.as_os_str().is_empty())
+            .map(|p| String::from(p.to_str().unwrap()))
+            .collect();
+        if paths.len() < 2 {
+            return Ok(None);
+        }
+        paths
+    };
+
+    let format = get_file_format(&paths[0]);
+    if format == FileFormat::TodoTxt {
+        return Err(format!(
+            "Only Markdown and Org task files can be aggregated: {}",
+            path
+        ));
+    }
+    if paths.iter().any(|p| get_file_format(p) != format) {
+        return Err(format!(
+            "The aggregated task files must have the same format: {}",
+            path
+        ));
+    }
+
+    Ok(Some(paths))
+}
+
+// Every file of an aggregated task list gets the lines following its marker
+// line; only the changed files are written
+fn set_aggregated_contents(paths: &[String], contents: &str) -> Result<(), String> {
+    // like a task added on top of the list, the lines before the first marker
+    // go to the first file
+    let mut lines_before_markers = String::new();
+    let mut files_contents: Vec<String> = Vec::new();
+    for line in contents.split_inclusive('\n') {
+        if text_is_file_marker(line.trim_end()) {
+            files_contents.push(String::new());
+        } else {
+            match files_contents.last_mut() {
+                Some(file_content) => file_content.push_str(line),
+                None => lines_before_markers.push_str(line),
+            }
+        }
+    }
+
+    if files_contents.len() != paths.len() {
+        return Err(String::from(
+            "The file markers of the aggregated task list were changed; nothing was written",
+        ));
+    }
+    files_contents[0].insert_str(0, &lines_before_markers);
+
+    for (index, (path, file_content)) in paths.iter().zip(files_contents.iter_mut()).enumerate() {
+        let current_content = read_file(path)?;
+        let bom = if current_content.starts_with('\u{feff}') {
+            "\u{feff}"
+        } else {
+            ""
+        };
+        let current_content = &current_content[bom.len()..];
+        if index + 1 < paths.len()
+            && !current_content.is_empty()
+            && !current_content.ends_with('\n')
+            && file_content.ends_with('\n')
+        {
+            file_content.pop();
+        }
+
+        if *file_content == current_content {
+            continue;
+        }
+
+        // the BOM of the file is kept
+        file_content.insert_str(0, bom);
+        if let Err(e) = write_file_atomically(path, file_content) {
+            return Err(format!("Unable to write file {}: {}", path, e));
+        }
+    }
+
+    Ok(())
+}
+
+// The contents are written to a temporary file next to the target, synced to
+// disk, then renamed over the target: an interrupted write leaves the previous
+// file untouched. A symlinked file is replaced at its target, keeping the
+// symlink, and the permissions of the replaced file are kept.
+fn write_file_atomically(path: &str, contents: &str) -> std::io::Result<()> {
+    let target = match fs::canonicalize(path) {
+        Ok(target) => target,
+        Err(_) => PathBuf::from(path),
+    };
+    let dir = match target.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let file_name = match target.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => return Err(std::io::Error::other("invalid file path")),
+    };
+    let tmp_path = dir.join(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    let written = (|| {
+        let mut tmp_file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)?;
+        tmp_file.write_all(contents.as_bytes())?;
+        if let Ok(metadata) = fs::metadata(&target) {
+            tmp_file.set_permissions(metadata.permissions())?;
+        }
+        tmp_file.sync_all()?;
+        fs::rename(&tmp_path, &target)
+    })();
+
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+        return written;
+    }
+
+    // the rename itself is durable once the directory is synced; not every
+    // platform can open a directory, hence the ignored errors
+    if let Ok(dir_file) = File::open(&dir) {
+        let _ = dir_file.sync_all();
+    }
+
+    Ok(())
+}
+
+// A file that does not exist until something is written to it: the time log
+// does not exist until some time has been tracked, the archive until tasks are
+// pruned
+pub struct OptionalFileHandlerReal {
+    pub path: String,
+}
+
+impl ContentGetter for OptionalFileHandlerReal {
+    fn get_contents(&self) -> Result<String, String> {
+        if !Path::new(&self.path).exists() {
+            return Ok(String::from(""));
+        }
+
+        match fs::read_to_string(&self.path) {
+            Ok(content) => Ok(content),
+            Err(_) => Err(format!("Could not read file {}", &self.path)),
+        }
+    }
+}
+
+impl ContentSetter for OptionalFileHandlerReal {
+    fn set_contents(&mut self, contents: String) -> Result<(), String> {
+        match write_file_atomically(&self.path, &contents) {
             Ok(_) => Ok(()),
-            Err(_) => Err(String::from("Unable to write file")),
+            Err(_) => Err(format!("Unable to write file {}", &self.path)),
         }
     }
 }
 
-pub struct UserCmdRunnerReal {
-    pub taxfile_path_getter: &'static dyn TaxfilePathGetter,
+// Advisory lock serializing the commands that change the task file, taken on
+// a sidecar file for the whole read-modify-write of a command, and released
+// when dropped. The lock file is never removed: another process may be
+// waiting on it.
+pub struct FileLockReal {
+    _file: File,
+}
+
+impl FileLockReal {
+    pub fn lock(path: &str) -> Result<Self, String> {
+        let file = match fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+        {
+            Ok(file) => file,
+            Err(e) => return Err(format!("Could not open lock file {}: {}", path, e)),
+        };
+
+        match file.lock() {
+            Ok(_) => Ok(FileLockReal { _file: file }),
+            Err(e) => Err(format!("Could not lock file {}: {}", path, e)),
+        }
+    }
+}
+
+pub struct UserCmdRunnerReal<'a> {
+    pub taxfile_path_getter: &'a dyn TaxfilePathGetter,
     pub get_env: EnvGetter,
+    // lock held while the command runs; the commands run by the hooks share
+    // it instead of waiting for it forever
+    pub held_lock_path: Option<String>,
 }
 
 pub trait UserCmdRunner {
@@ -177,14 +730,23 @@ pub trait UserCmdRunner {
     fn run(&self, cmd: &mut Command) -> Result<(), String>;
 }
 
-impl UserCmdRunner for UserCmdRunnerReal {
+impl UserCmdRunner for UserCmdRunnerReal<'_> {
     fn env_single_task<'a>(&self, task: &Task, cmd: &'a mut Command) -> &'a mut Command {
         cmd.env("TAX_TASK_NUM", format!("{}", task.num))
+            .env("TAX_TASK_ID", task.id.as_deref().unwrap_or(""))
             .env("TAX_TASK_NAME", &task.name)
             .env("TAX_TASK_PLAIN_NAME", &task.plain_name)
             .env("TAX_TASK_LINE", &task.line)
             .env("TAX_TASK_LINE_NUM", format!("{}", task.line_num))
-            .env("TAX_TASK_CHECKED", if task.is_checked { "1" } else { "0" })
+            .env(
+                "TAX_TASK_CHECKED",
+                if task.state == TaskState::Done {
+                    "1"
+                } else {
+                    "0"
+                },
+            )
+            .env("TAX_TASK_STATE", state_to_env_value(task.state))
             .env("TAX_TASK_FOCUSED", if task.is_focused { "1" } else { "0" })
     }
 
@@ -208,6 +770,9 @@ impl UserCmdRunner for UserCmdRunnerReal {
                     .env("TAX_CMD", cmd)
                     .env("TAX_OPERATION", operation)
                     .env("TAX_MESSAGE", message);
+                if let Some(lock_path) = &self.held_lock_path {
+                    cmd_obj.env("TAX_LOCK_HELD", lock_path);
+                }
                 Ok(Some(cmd_obj))
             }
             None => Ok(None),
@@ -225,6 +790,17 @@ impl UserCmdRunner for UserCmdRunnerReal {
     }
 }
 
+fn state_to_env_value(state: TaskState) -> &'static str {
+    match state {
+        TaskState::Open => "OPEN",
+        TaskState::Done => "DONE",
+        TaskState::Cancelled => "CANCELLED",
+        TaskState::InProgress => "IN_PROGRESS",
+        TaskState::Deferred => "DEFERRED",
+    }
+}
+
+#[allow(clippy::manual_filter)]
 fn get_env_var_if_not_empty(name: &str, get_env: EnvGetter) -> Option<String> {
     match (get_env)(name) {
         Some(v) => {
@@ -242,13 +818,18 @@ fn get_env_var_if_not_empty(name: &str, get_env: EnvGetter) -> Option<String> {
 mod tests {
 
     use super::*;
-    use crate::test_helpers::test::{env_getter_none, env_getter_taxfile, home_getter_guybrush};
+    use crate::test_helpers::test::{
+        config_dir_getter_guybrush, env_getter_none, env_getter_taxfile, home_getter_guybrush,
+    };
 
     #[test]
     fn test_taxfile_path_getter_real() {
         let path_getter_noenv = &TaxfilePathGetterReal {
             get_env: env_getter_none,
             get_home: home_getter_guybrush,
+            get_config_dir: config_dir_getter_guybrush,
+            file: None,
+            list: None,
         };
         assert_eq!(
             path_getter_noenv.get_taxfile_path(),
@@ -258,11 +839,313 @@ mod tests {
         let path_getter_yesenv = &TaxfilePathGetterReal {
             get_env: env_getter_taxfile,
             get_home: home_getter_guybrush,
+            get_config_dir: config_dir_getter_guybrush,
+            file: None,
+            list: None,
         };
 
         assert_eq!(
             path_getter_yesenv.get_taxfile_path(),
             Ok(String::from("/path/to/overriden/taxfile"))
         );
+
+        assert_eq!(
+            path_getter_noenv.get_timelog_path(),
+            Ok(String::from("/home/guybrush/.tasks.md.timelog"))
+        );
+        assert_eq!(
+            path_getter_noenv.get_lock_path(),
+            Ok(String::from("/home/guybrush/.tasks.md.lock"))
+        );
+
+        let path_getter_file = &TaxfilePathGetterReal {
+            get_env: env_getter_taxfile,
+            get_home: home_getter_guybrush,
+            get_config_dir: config_dir_getter_guybrush,
+            file: Some(String::from("/path/to/given/taxfile")),
+            list: None,
+        };
+        assert_eq!(
+            path_getter_file.get_taxfile_path(),
+            Ok(String::from("/path/to/given/taxfile"))
+        );
+    }
+
+    #[test]
+    fn test_user_cmd_runner_real_env_single_task() {
+        let user_cmd_runner = UserCmdRunnerReal {
+            taxfile_path_getter: &TaxfilePathGetterReal {
+                get_env: env_getter_none,
+                get_home: home_getter_guybrush,
+                get_config_dir: config_dir_getter_guybrush,
+                file: None,
+                list: None,
+            },
+            get_env: env_getter_none,
+            held_lock_path: None,
+        };
+        let task = Task {
+            num: 2,
+            name: String::from("Write code ^ab12"),
+            id: Some(String::from("ab12")),
+            ..Default::default()
+        };
+
+        let mut cmd = Command::new("sh");
+        let envs: Vec<(String, String)> = user_cmd_runner
+            .env_single_task(&task, &mut cmd)
+            .get_envs()
+            .map(|(key, value)| {
+                (
+                    key.to_string_lossy().to_string(),
+                    value.unwrap().to_string_lossy().to_string(),
+                )
+            })
+            .collect();
+
+        assert!(envs.contains(&(String::from("TAX_TASK_NUM"), String::from("2"))));
+        assert!(envs.contains(&(String::from("TAX_TASK_ID"), String::from("ab12"))));
+    }
+
+    #[test]
+    fn test_get_file_format() {
+        assert_eq!(get_file_format("/path/to/tasks.md"), FileFormat::Markdown);
+        assert_eq!(get_file_format("/path/to/tasks.org"), FileFormat::Org);
+        assert_eq!(get_file_format("/path/to/todo.txt"), FileFormat::TodoTxt);
+        assert_eq!(get_file_format("/path/to/Done.txt"), FileFormat::TodoTxt);
+        assert_eq!(get_file_format("/path/to/notes.txt"), FileFormat::Markdown);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_file_atomically() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = env::temp_dir().join(format!("tax-test-write-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("tasks.md");
+        let link = dir.join("link.md");
+        fs::write(&target, "- [ ] Old task\n").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+        symlink(&target, &link).unwrap();
+
+        let mut handler = ContentHandlerReal {
+            path: String::from(link.to_str().unwrap()),
+            read_hash: Rc::default(),
+        };
+        handler
+            .set_contents(String::from("- [ ] New task\n"))
+            .unwrap();
+
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "- [ ] New task\n");
+        assert_eq!(
+            fs::metadata(&target).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_content_handler_real_changed_file() {
+        let path = env::temp_dir().join(format!("tax-test-changed-{}.md", std::process::id()));
+        fs::write(&path, "- [ ] Fix the roof\n").unwrap();
+
+        let read_hash = Rc::default();
+        let getter = ContentHandlerReal {
+            path: String::from(path.to_str().unwrap()),
+            read_hash: Rc::clone(&read_hash),
+        };
+        let mut setter = ContentHandlerReal {
+            path: String::from(path.to_str().unwrap()),
+            read_hash,
+        };
+
+        getter.get_contents().unwrap();
+        setter
+            .set_contents(String::from("- [x] Fix the roof\n"))
+            .unwrap();
+        assert_eq!(
+            getter.get_contents(),
+            Ok(String::from("- [x] Fix the roof\n"))
+        );
+
+        // changed by an editor in the meantime
+        fs::write(&path, "- [ ] Call mom\n- [x] Fix the roof\n").unwrap();
+        assert!(getter.get_contents().is_err());
+        assert!(setter
+            .set_contents(String::from("- [ ] Fix the roof\n"))
+            .is_err());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "- [ ] Call mom\n- [x] Fix the roof\n"
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_taxfile_path_getter_real_lists() {
+        // a getter cannot capture the path: it builds it again
+        fn get_config_dir() -> Option<PathBuf> {
+            Some(env::temp_dir().join(format!("tax-test-config-{}", std::process::id())))
+        }
+        let config_dir = get_config_dir().unwrap();
+        fs::create_dir_all(config_dir.join("tax")).unwrap();
+        fs::write(
+            config_dir.join("tax").join("lists.yml"),
+            "work: ~/work/tasks.md\nhome: home.md\n",
+        )
+        .unwrap();
+
+        let path_getter = &TaxfilePathGetterReal {
+            get_env: env_getter_taxfile,
+            get_home: home_getter_guybrush,
+            get_config_dir,
+            file: None,
+            list: Some(String::from("work")),
+        };
+        assert_eq!(
+            path_getter.get_taxfile_path(),
+            Ok(String::from("/home/guybrush/work/tasks.md"))
+        );
+        assert_eq!(
+            path_getter.get_lists().unwrap()[1].1,
+            String::from(config_dir.join("tax").join("home.md").to_str().unwrap())
+        );
+
+        let path_getter = &TaxfilePathGetterReal {
+            get_env: env_getter_taxfile,
+            get_home: home_getter_guybrush,
+            get_config_dir,
+            file: None,
+            list: Some(String::from("someday")),
+        };
+        assert!(path_getter.get_taxfile_path().is_err());
+
+        fs::remove_dir_all(&config_dir).unwrap();
+    }
+
+    #[test]
+    fn test_content_handler_real_aggregated() {
+        let dir = env::temp_dir().join(format!("tax-test-aggregated-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("work.md"), "- [ ] Fix the API").unwrap();
+        fs::write(dir.join("home.md"), "\u{feff}# Perso\n- [ ] Fix the roof\n").unwrap();
+        fs::write(dir.join(".tasks.md.lock"), "").unwrap();
+        fs::write(dir.join("notes.txt"), "Not a task file").unwrap();
+
+        let mut handler = ContentHandlerReal {
+            path: String::from(dir.to_str().unwrap()),
+            read_hash: Rc::default(),
+        };
+        let content = handler.get_contents().unwrap();
+        assert_eq!(
+            content,
+            "<!-- tax file: home.md -->\n# Perso\n- [ ] Fix the roof\n<!-- tax file: work.md -->\n- [ ] Fix the API"
+        );
+
+        handler
+            .set_contents(content.replace("[ ] Fix the API", "[x] Fix the API") + "\n- [ ] Deploy")
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("work.md")).unwrap(),
+            "- [x] Fix the API\n- [ ] Deploy"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("home.md")).unwrap(),
+            "\u{feff}# Perso\n- [ ] Fix the roof\n"
+        );
+
+        // a colon-separated list, in its own order
+        let mut handler = ContentHandlerReal {
+            path: format!(
+                "{}:{}",
+                dir.join("work.md").to_str().unwrap(),
+                dir.join("home.md").to_str().unwrap()
+            ),
+            read_hash: Rc::default(),
+        };
+        let content = handler.get_contents().unwrap();
+        assert_eq!(
+            content,
+            "<!-- tax file: work.md -->\n- [x] Fix the API\n- [ ] Deploy\n<!-- tax file: home.md -->\n# Perso\n- [ ] Fix the roof\n"
+        );
+
+        // a task added on top of the list goes to the first file
+        handler
+            .set_contents(format!(
+                "- [ ] Call the bank\n{}",
+                content.replace("[ ] Fix the roof", "[x] Fix the roof")
+            ))
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("work.md")).unwrap(),
+            "- [ ] Call the bank\n- [x] Fix the API\n- [ ] Deploy"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("home.md")).unwrap(),
+            "\u{feff}# Perso\n- [x] Fix the roof\n"
+        );
+
+        // the written lines were moved to the first file: read back, they are
+        // not taken for a change by another program
+        assert_eq!(
+            handler.get_contents(),
+            Ok(String::from(
+                "<!-- tax file: work.md -->\n- [ ] Call the bank\n- [x] Fix the API\n- [ ] Deploy\n<!-- tax file: home.md -->\n# Perso\n- [x] Fix the roof\n"
+            ))
+        );
+
+        assert!(handler
+            .set_contents(String::from("- [ ] No markers\n"))
+            .is_err());
+
+        // the sidecar files of a directory are inside it
+        let path_getter = &TaxfilePathGetterReal {
+            get_env: env_getter_none,
+            get_home: home_getter_guybrush,
+            get_config_dir: config_dir_getter_guybrush,
+            file: Some(String::from(dir.to_str().unwrap())),
+            list: None,
+        };
+        assert_eq!(
+            path_getter.get_lock_path(),
+            Ok(String::from(
+                dir.join(format!(".tax-test-aggregated-{}.lock", std::process::id()))
+                    .to_str()
+                    .unwrap()
+            ))
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_get_aggregated_paths_archive() {
+        let dir = env::temp_dir().join(format!("tax-test-archive-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("work.md"),
+            "---\narchive: done.md\n---\n- [ ] Deploy\n",
+        )
+        .unwrap();
+        fs::write(dir.join("home.md"), "- [ ] Fix the roof\n").unwrap();
+        fs::write(dir.join("done.md"), "- [x] Fix the API\n").unwrap();
+
+        assert_eq!(
+            get_aggregated_paths(dir.to_str().unwrap()),
+            Ok(Some(vec![
+                String::from(dir.join("home.md").to_str().unwrap()),
+                String::from(dir.join("work.md").to_str().unwrap()),
+            ]))
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
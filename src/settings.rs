@@ -0,0 +1,303 @@
+use crate::services::EnvGetter;
+use crate::tasks::text_get_front_matter;
+use colored::Color;
+
+pub enum RecurMode {
+    // a fresh copy of a checked recurring task is inserted after it
+    Insert,
+    // a checked recurring task is replaced by its fresh copy
+    Replace,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stamp {
+    None,
+    Date,
+    DateTime,
+}
+
+pub struct Settings {
+    pub recur_mode: RecurMode,
+    pub stamp_done: Stamp,
+    pub stamp_created: Stamp,
+    // section where tasks are added when none is given nor focused
+    pub default_section: Option<String>,
+    // minutes between two tasks of `tax cycle`
+    pub cycle_minutes: u64,
+    // (tag, color name)
+    pub tag_colors: Vec<(String, String)>,
+    // file where `tax prune` moves the closed tasks, relative to the task file
+    pub archive: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            recur_mode: RecurMode::Insert,
+            stamp_done: Stamp::None,
+            stamp_created: Stamp::None,
+            default_section: None,
+            cycle_minutes: 1,
+            tag_colors: Vec::new(),
+            archive: None,
+        }
+    }
+}
+
+pub fn get_settings(get_env: EnvGetter) -> Result<Settings, String> {
+    let mut settings = Settings::default();
+
+    if let Some(recur_mode) = get_env("TAX_RECUR_MODE") {
+        settings.recur_mode = parse_recur_mode("TAX_RECUR_MODE", &recur_mode)?;
+    }
+
+    if let Some(stamp_done) = get_env("TAX_STAMP_DONE") {
+        settings.stamp_done = parse_stamp("TAX_STAMP_DONE", &stamp_done)?;
+    }
+
+    if let Some(stamp_created) = get_env("TAX_STAMP_CREATED") {
+        settings.stamp_created = parse_stamp("TAX_STAMP_CREATED", &stamp_created)?;
+    }
+
+    Ok(settings)
+}
+
+// Settings of the YAML front matter of the task file, which take precedence
+// over the environment:
+//
+// ---
+// section: Inbox
+// cycle: 5
+// stamp_done: date
+// tag_colors:
+//   urgent: red
+// archive: done.md
+// ---
+//
+// Only flat keys and one level of mapping are understood; other keys are left
+// to the other tools reading the front matter.
+pub fn get_file_settings(mut settings: Settings, content: &str) -> Result<Settings, String> {
+    let front_matter = match text_get_front_matter(content) {
+        Some((lines, _)) => lines,
+        None => return Ok(settings),
+    };
+
+    // key of the mapping being read
+    let mut mapping_key: Option<String> = None;
+
+    for line in front_matter {
+        let line = match line.find(" #") {
+            Some(comment_start) => &line[..comment_start],
+            None => line,
+        };
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (text_unquote(key.trim()), text_unquote(value.trim())),
+            None => return Err(format!("Invalid front matter line \"{}\"", line)),
+        };
+
+        if line.starts_with(char::is_whitespace) {
+            if mapping_key.as_deref() == Some("tag_colors") {
+                if value.parse::<Color>().is_err() {
+                    return Err(format!(
+                        "Invalid color \"{}\" for tag \"{}\" in front matter",
+                        value, key
+                    ));
+                }
+                settings
+                    .tag_colors
+                    .push((key.trim_start_matches('#').to_string(), value.to_string()));
+            }
+            continue;
+        }
+
+        mapping_key = Some(key.to_string());
+        match key {
+            "section" => settings.default_section = Some(value.to_string()),
+            "cycle" => {
+                settings.cycle_minutes = match value.trim_end_matches('m').parse::<u64>() {
+                    Ok(minutes) if minutes > 0 => minutes,
+                    _ => {
+                        return Err(format!(
+                            "Invalid cycle \"{}\" in front matter; expected a number of minutes",
+                            value
+                        ))
+                    }
+                }
+            }
+            "recur_mode" => settings.recur_mode = parse_recur_mode("recur_mode", value)?,
+            "stamp_done" => settings.stamp_done = parse_stamp("stamp_done", value)?,
+            "stamp_created" => settings.stamp_created = parse_stamp("stamp_created", value)?,
+            "archive" => settings.archive = Some(value.to_string()),
+            _ => (),
+        }
+    }
+
+    Ok(settings)
+}
+
+// Named task lists of the lists file, one `name: path` line per list:
+//
+// work: ~/work/tasks.md
+// home: "~/Dropbox/home tasks.md"
+pub fn parse_lists(content: &str) -> Result<Vec<(String, String)>, String> {
+    let mut lists: Vec<(String, String)> = Vec::new();
+
+    for line in content.lines() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        match line.split_once(':') {
+            Some((name, path)) if !text_unquote(path.trim()).is_empty() => lists.push((
+                text_unquote(name.trim()).to_string(),
+                text_unquote(path.trim()).to_string(),
+            )),
+            _ => return Err(format!("Invalid list line \"{}\"", line)),
+        }
+    }
+
+    Ok(lists)
+}
+
+fn text_unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(unquoted) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return unquoted;
+        }
+    }
+    value
+}
+
+fn parse_recur_mode(name: &str, value: &str) -> Result<RecurMode, String> {
+    match value.trim() {
+        "insert" | "" => Ok(RecurMode::Insert),
+        "replace" => Ok(RecurMode::Replace),
+        _ => Err(format!(
+            "Invalid {} \"{}\"; expected insert or replace",
+            name, value
+        )),
+    }
+}
+
+fn parse_stamp(name: &str, value: &str) -> Result<Stamp, String> {
+    match value.trim() {
+        "" | "none" | "false" => Ok(Stamp::None),
+        "date" | "true" => Ok(Stamp::Date),
+        "datetime" => Ok(Stamp::DateTime),
+        _ => Err(format!(
+            "Invalid {} \"{}\"; expected none, date or datetime",
+            name, value
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::test::env_getter_none;
+
+    #[test]
+    fn test_get_settings() {
+        match get_settings(env_getter_none) {
+            Ok(settings) => {
+                assert!(matches!(settings.recur_mode, RecurMode::Insert));
+                assert_eq!(settings.stamp_done, Stamp::None);
+            }
+            Err(e) => panic!("{}", e),
+        }
+
+        match get_settings(|name| match name {
+            "TAX_RECUR_MODE" => Some(String::from("replace")),
+            "TAX_STAMP_DONE" => Some(String::from("datetime")),
+            "TAX_STAMP_CREATED" => Some(String::from("date")),
+            _ => None,
+        }) {
+            Ok(settings) => {
+                assert!(matches!(settings.recur_mode, RecurMode::Replace));
+                assert_eq!(settings.stamp_done, Stamp::DateTime);
+                assert_eq!(settings.stamp_created, Stamp::Date);
+            }
+            Err(e) => panic!("{}", e),
+        }
+
+        assert!(get_settings(|name| match name {
+            "TAX_RECUR_MODE" => Some(String::from("sometimes")),
+            _ => None,
+        })
+        .is_err());
+
+        assert!(get_settings(|name| match name {
+            "TAX_STAMP_DONE" => Some(String::from("yes")),
+            _ => None,
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_get_file_settings() {
+        let content = [
+            "---",
+            "title: Tasks",
+            "section: \"Inbox\"",
+            "cycle: 5m",
+            "stamp_done: datetime # when checked",
+            "tag_colors:",
+            "  urgent: red",
+            "  \"#home\": bright blue",
+            "archive: done.md",
+            "---",
+            "# Inbox",
+        ]
+        .join("\n");
+
+        match get_file_settings(Settings::default(), &content) {
+            Ok(settings) => {
+                assert_eq!(settings.default_section, Some(String::from("Inbox")));
+                assert_eq!(settings.cycle_minutes, 5);
+                assert_eq!(settings.stamp_done, Stamp::DateTime);
+                assert_eq!(
+                    settings.tag_colors,
+                    vec![
+                        (String::from("urgent"), String::from("red")),
+                        (String::from("home"), String::from("bright blue"))
+                    ]
+                );
+                assert_eq!(settings.archive, Some(String::from("done.md")));
+            }
+            Err(e) => panic!("{}", e),
+        }
+
+        // no front matter: settings are left untouched
+        match get_file_settings(Settings::default(), "# Inbox\ncycle: 5") {
+            Ok(settings) => assert_eq!(settings.cycle_minutes, 1),
+            Err(e) => panic!("{}", e),
+        }
+
+        assert!(get_file_settings(Settings::default(), "---\ncycle: often\n---").is_err());
+        assert!(get_file_settings(
+            Settings::default(),
+            "---\ntag_colors:\n  urgent: reddish\n---"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_lists() {
+        assert_eq!(
+            parse_lists("# my lists\nwork: ~/work/tasks.md\n\nhome: \"home tasks.md\"\n"),
+            Ok(vec![
+                (String::from("work"), String::from("~/work/tasks.md")),
+                (String::from("home"), String::from("home tasks.md")),
+            ])
+        );
+        assert!(parse_lists("work ~/work/tasks.md").is_err());
+        assert!(parse_lists("work:").is_err());
+    }
+}
@@ -1,33 +1,82 @@
-use crate::model::{Section, Task};
+use crate::model::{FileFormat, Recurrence, Section, Task, TaskState, TimeInterval};
 use crate::services::ContentGetter;
+use crate::settings::Stamp;
+use chrono::{Datelike, Days, Months, NaiveDate, NaiveDateTime, Weekday};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
 use regex::Regex;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use std::time::SystemTime;
 
 lazy_static! {
     static ref TASK_LINE_REGEX: Regex =
-        Regex::new(r"(?m)^\s*(?:-|\*)\s+\[(x|\s*|>)\]\s+(.+?)$").unwrap();
-    static ref TASK_NAME_FOCUSED_REGEX: Regex = Regex::new(r"(?m)\*\*.+\*\*").unwrap();
+        Regex::new(r"(?m)^(\s*)(?:[-*+]|\d{1,9}[.)])\s+\[(x|X|-|/|>|\s*)\]\s+(.+?)$").unwrap();
+    static ref TASK_NAME_FOCUSED_REGEX: Regex = Regex::new(r"(?m)^\*\*.+\*\*$").unwrap();
     static ref COMMENT_REGEX: Regex = Regex::new(r"(?m)^(.*?)[^:]//(.+?)$").unwrap();
     static ref HEADER_REGEX: Regex = Regex::new(r"(?m)^(#{1,6})\s+(.*?)$").unwrap();
+    static ref TAG_REGEX: Regex = Regex::new(r"(?:^|\s)#([[:alpha:]][\w-]*)").unwrap();
+    static ref CONTEXT_REGEX: Regex = Regex::new(r"(?:^|\s)@([[:alpha:]][\w-]*)").unwrap();
+    static ref PRIORITY_REGEX: Regex =
+        Regex::new(r"^\(([A-Z])\)(?:\s|$)|(?:^|\s)(!{1,3})(?:\s|$)|^\[#([A-Z])\](?:\s|$)").unwrap();
+    static ref ORG_TAGS_REGEX: Regex = Regex::new(r"(?:^|\s):((?:[\w@#%]+:)+)\s*$").unwrap();
+    static ref ORG_HEADLINE_REGEX: Regex = Regex::new(r"^(\*+)\s+(.*?)\s*$").unwrap();
+    static ref ORG_TASK_REGEX: Regex =
+        Regex::new(r"^(\*+)\s+(TODO|DONE|STARTED|WAITING|CANCELLED|CANCELED)(?:\s+(.*?))?\s*$")
+            .unwrap();
+    static ref ORG_PLANNING_REGEX: Regex =
+        Regex::new(r"(DEADLINE|SCHEDULED):\s*<(\d{4}-\d{2}-\d{2})[^>]*>").unwrap();
+    static ref ORG_FOCUSED_REGEX: Regex = Regex::new(r"^\*[^*\s](?:.*[^*\s])?\*$").unwrap();
+    static ref ORG_BLOCK_BEGIN_REGEX: Regex = Regex::new(r"(?i)^\s*#\+begin_").unwrap();
+    static ref ORG_BLOCK_END_REGEX: Regex = Regex::new(r"(?i)^\s*#\+end_").unwrap();
+    static ref RECURRENCE_REGEX: Regex =
+        Regex::new(r"(?:^|\s)every:(day|weekday|week|month|year|monthday\s+(\d{1,2}))(?:\s|$)")
+            .unwrap();
+    static ref DONE_REGEX: Regex =
+        Regex::new(r"(?:^|\s)done:(\d{4}-\d{2}-\d{2})(?:T\d{2}:\d{2}(?::\d{2})?)?\b").unwrap();
+    static ref CREATED_REGEX: Regex =
+        Regex::new(r"(?:^|\s)created:(\d{4}-\d{2}-\d{2})(?:T\d{2}:\d{2}(?::\d{2})?)?\b").unwrap();
+    static ref ID_REGEX: Regex = Regex::new(r"(?:^|\s)\^([0-9a-z]{4,})$").unwrap();
+    static ref AFTER_REGEX: Regex = Regex::new(r"(?:^|\s)after:\^?([0-9a-z]{4,})\b").unwrap();
+    static ref ESTIMATE_REGEX: Regex =
+        Regex::new(r"(?:^|\s)~(?:(\d+)h)?(?:(\d+)m)?(?:\s|$)").unwrap();
+    static ref DUE_REGEX: Regex = Regex::new(r"(?:^|\s)due:(\d{4}-\d{2}-\d{2})\b").unwrap();
+    static ref TODOTXT_LINE_REGEX: Regex = Regex::new(
+        r"^(?:(x)\s+(?:(\d{4}-\d{2}-\d{2})\s+)?)?(?:(\([A-Z]\))\s+)?(?:(\d{4}-\d{2}-\d{2})\s+)?(.*)$"
+    )
+    .unwrap();
+    static ref TODOTXT_STATE_REGEX: Regex =
+        Regex::new(r"(?:^|\s)state:(started|waiting|cancelled)\b").unwrap();
+    static ref PROJECT_REGEX: Regex = Regex::new(r"(?:^|\s)\+([^\s+]+)").unwrap();
+    static ref TODOTXT_FOCUS_REGEX: Regex =
+        Regex::new(r"^#\s*focus:((?:\s*\+\S+)+)\s*$").unwrap();
+    static ref METADATA_TOKEN_REGEX: Regex =
+        Regex::new(r"^(?:\^[0-9a-z]{4,}|[[:alpha:]][\w-]*:[^\s/]\S*)$").unwrap();
+    static ref FILE_MARKER_REGEX: Regex = Regex::new(r"^<!-- tax file: (.+) -->\s*$").unwrap();
 }
 
 pub fn get_current_task(
     content_getter: &dyn ContentGetter,
-    cycle: bool,
+    today: NaiveDate,
+    cycle_minutes: Option<u64>,
 ) -> Result<Option<(Task, bool)>, String> {
-    let (open_tasks, use_sections, _, focused_section) = get_open_tasks(content_getter)?;
+    let (open_tasks, use_sections, _, focused_sections) = get_open_tasks(content_getter)?;
 
-    let section_tasks = if use_sections && focused_section != None {
-        open_tasks
-            .into_iter()
-            .filter(|t| filter_task_in_section_cbk(t, focused_section.clone().unwrap().as_ref()))
-            .collect()
+    let section_tasks: Vec<Task> = if use_sections && !focused_sections.is_empty() {
+        filter_tasks_in_sections(&open_tasks, &focused_sections)
     } else {
         open_tasks
     };
 
-    let focused_tasks = filter_focused_tasks(&section_tasks, true);
+    // deferred and blocked tasks are never current
+    let section_tasks: Vec<Task> = section_tasks
+        .into_iter()
+        .filter(|t| t.state != TaskState::Deferred && !t.is_blocked)
+        .collect();
+
+    let focused_tasks = sort_tasks_by_priority(&sort_tasks_by_due(&filter_focused_tasks(
+        &section_tasks,
+        true,
+    )));
 
     if !focused_tasks.is_empty() {
         return Ok(Some((focused_tasks[0].clone(), use_sections)));
@@ -37,10 +86,29 @@ pub fn get_current_task(
         return Ok(None);
     }
 
-    if cycle {
+    let started_tasks: Vec<Task> = sort_tasks_by_priority(&section_tasks)
+        .into_iter()
+        .filter(|t| t.state == TaskState::InProgress)
+        .collect();
+
+    if !started_tasks.is_empty() {
+        return Ok(Some((started_tasks[0].clone(), use_sections)));
+    }
+
+    // tasks due today or overdue take precedence over the others
+    let urgent_tasks = sort_tasks_by_due(&filter_due_tasks(&section_tasks, today));
+
+    if !urgent_tasks.is_empty() {
+        return Ok(Some((urgent_tasks[0].clone(), use_sections)));
+    }
+
+    // only the tasks of the highest priority are candidates
+    let section_tasks = filter_highest_priority_tasks(&section_tasks);
+
+    if let Some(cycle_minutes) = cycle_minutes {
         let now = SystemTime::now();
         let minutes = match now.duration_since(SystemTime::UNIX_EPOCH) {
-            Ok(n) => n.as_secs() / 60,
+            Ok(n) => n.as_secs() / 60 / cycle_minutes,
             Err(_) => 0,
         };
 
@@ -55,7 +123,8 @@ pub fn get_current_task(
     Ok(Some((section_tasks[0].clone(), use_sections)))
 }
 
-type AllTasks = (Vec<Task>, bool, Vec<Rc<Section>>, Option<Rc<Section>>);
+// (tasks, use sections, sections, focused sections)
+type AllTasks = (Vec<Task>, bool, Vec<Rc<Section>>, Vec<Rc<Section>>);
 
 pub fn get_all_tasks(
     content_getter: &dyn ContentGetter,
@@ -67,14 +136,39 @@ pub fn get_all_tasks(
     let mut task_num = 1;
     let mut line_num = 1;
     let mut current_section: Option<Rc<Section>> = None;
-    let mut focused_section: Option<Rc<Section>> = None;
 
     let mut building_section: Option<Rc<Section>> = None;
+    // sections enclosing the current line
+    let mut sections_stack: Vec<Rc<Section>> = Vec::new();
+
+    // (nesting, task num) of the tasks enclosing the current line; nesting is
+    // (headline level, indent width), so that list items nest under headlines
+    let mut parents_stack: Vec<((usize, usize), usize)> = Vec::new();
+
+    // blank lines are part of a task body only if followed by more body lines
+    let mut building_body = false;
+    let mut pending_blank_lines: Vec<String> = Vec::new();
+
+    let content = content_getter.get_contents()?;
+    let content = content.strip_prefix('\u{feff}').unwrap_or(&content);
+    let format = content_getter.get_format();
+    let blocks = match format {
+        FileFormat::TodoTxt => return Ok(get_todotxt_tasks(content)),
+        _ => get_blocks(content, format),
+    };
+
+    // underline of a setext heading
+    let mut heading_line_num_end = 0;
 
-    for line in content_getter.get_contents()?.lines() {
-        match HEADER_REGEX.captures(line) {
+    for line in content.lines() {
+        if line_num <= heading_line_num_end {
+            line_num += 1;
+            continue;
+        }
+
+        match blocks.headings.get(&line_num) {
             None => (),
-            Some(cap) => {
+            Some((level, line_num_end)) => {
                 match building_section {
                     None => (), // first section being built,
                     Some(s) => {
@@ -83,24 +177,40 @@ pub fn get_all_tasks(
 
                         let section_rc = Rc::from(section);
 
-                        if section_rc.is_focused && focused_section == None {
-                            focused_section = Some(section_rc.clone());
-                        }
-
                         sections.push(section_rc);
                         section_num += 1;
                     }
                 }
 
-                let header_markup = cap[1].trim();
-                let section_name = cap[2].trim();
-                let is_focused = text_is_focused(section_name);
+                let section_name = match format {
+                    // the file of an aggregated task list
+                    _ if *level == 0 => match FILE_MARKER_REGEX.captures(line) {
+                        Some(cap) => text_file_marker_name(cap.get(1).unwrap().as_str()),
+                        None => line.trim(),
+                    },
+                    FileFormat::Org => match ORG_HEADLINE_REGEX.captures(line) {
+                        Some(cap) => cap.get(2).unwrap().as_str().trim(),
+                        None => line.trim(),
+                    },
+                    FileFormat::Markdown | FileFormat::TodoTxt => match HEADER_REGEX.captures(line)
+                    {
+                        Some(cap) => cap.get(2).unwrap().as_str().trim(),
+                        None => line.trim().trim_start_matches('#').trim(),
+                    },
+                };
+                let is_focused = text_is_focused(section_name, format);
                 let plain_name = if is_focused {
-                    text_remove_focus(section_name)
+                    text_remove_focus(section_name, format)
                 } else {
                     section_name.to_string()
                 };
 
+                // a heading is a subsection of the closest heading of a
+                // lower level
+                while sections_stack.last().is_some_and(|s| s.level >= *level) {
+                    sections_stack.pop();
+                }
+
                 let section = Rc::from(Section {
                     name: section_name.to_string(),
                     plain_name,
@@ -109,44 +219,108 @@ pub fn get_all_tasks(
                     line: line.to_string(),
                     line_num,
                     line_num_end: 0,
-                    level: header_markup.len(),
+                    level: *level,
+                    format,
+                    parent: sections_stack.last().cloned(),
+                    depth: sections_stack.len(),
                 });
 
+                heading_line_num_end = *line_num_end;
+                sections_stack.push(section.clone());
                 current_section = Some(section.clone());
                 building_section = Some(section);
+                parents_stack.clear();
+                building_body = false;
 
                 line_num += 1;
                 continue;
             }
         };
 
-        match TASK_LINE_REGEX.captures(line) {
-            None => (),
-            Some(cap) => {
-                let check_symbol = cap[1].trim();
-                let name = String::from(&cap[2]);
+        // (indent, state, name, headline level of Org TODO headlines)
+        let task_parts: Option<(String, TaskState, String, Option<usize>)> =
+            match blocks.headline_tasks.get(&line_num) {
+                Some(level) => ORG_TASK_REGEX.captures(line).map(|cap| {
+                    (
+                        String::new(),
+                        text_get_org_state(&cap[2]),
+                        cap.get(3).map_or("", |m| m.as_str()).to_string(),
+                        Some(*level),
+                    )
+                }),
+                None if blocks.list_items.contains(&line_num) => {
+                    TASK_LINE_REGEX.captures(line).map(|cap| {
+                        (
+                            cap[1].to_string(),
+                            text_get_state(cap[2].trim()),
+                            cap[3].to_string(),
+                            None,
+                        )
+                    })
+                }
+                None => None,
+            };
+
+        match task_parts {
+            None => {
+                if building_body {
+                    // a note belongs to the innermost task less indented than
+                    // itself, which may be the parent of the last task
+                    let indent_width = text_indent_width(text_get_indent(line));
+                    let owner_num = parents_stack.iter().rev().map(|(_, num)| *num).find(|num| {
+                        let task = &tasks[num - 1];
+                        task.org_level.is_some() || text_indent_width(&task.indent) < indent_width
+                    });
+
+                    if line.trim().is_empty() {
+                        pending_blank_lines.push(line.to_string());
+                    } else if let Some(owner_num) = owner_num {
+                        let task = &mut tasks[owner_num - 1];
+                        // the planning line of an Org headline comes first
+                        if task.org_level.is_some() && task.body.is_empty() && task.due.is_none() {
+                            task.due = text_get_org_planning_due(line);
+                        }
+                        task.body.append(&mut pending_blank_lines);
+                        task.body.push(line.to_string());
+                        task.line_num_end = line_num;
+                    } else {
+                        building_body = false;
+                    }
+                }
+            }
+            Some((indent, state, name, org_level)) => {
                 let trimmed_name = name.trim();
 
                 let (name_without_comment, comment) = text_get_comment(trimmed_name);
 
-                let is_task_focused = text_is_focused(name_without_comment.as_str());
+                let nesting = (org_level.unwrap_or(usize::MAX), text_indent_width(&indent));
+                while let Some((parent_nesting, _)) = parents_stack.last() {
+                    if *parent_nesting < nesting {
+                        break;
+                    }
+                    parents_stack.pop();
+                }
+                let parent = parents_stack.last().map(|(_, parent_num)| *parent_num);
+                let depth = parents_stack.len();
+                parents_stack.push((nesting, task_num));
+
                 tasks.push(Task {
-                    name: name_without_comment.clone(),
-                    plain_name: if is_task_focused {
-                        text_remove_focus(name_without_comment.as_str())
-                    } else {
-                        name_without_comment
-                    },
-                    comment,
                     num: task_num,
-                    is_checked: text_is_check_symbol(check_symbol),
+                    state,
                     line_num,
+                    line_num_end: line_num,
                     line: line.to_string(),
-                    is_focused: is_task_focused,
                     section: current_section.clone(),
+                    indent,
+                    depth,
+                    parent,
+                    org_level,
+                    ..task_from_name(&name_without_comment, comment, format)
                 });
 
                 task_num += 1;
+                building_body = true;
+                pending_blank_lines.clear();
             }
         }
 
@@ -161,42 +335,372 @@ pub fn get_all_tasks(
 
             let section_rc = Rc::from(section);
 
-            if section_rc.is_focused && focused_section == None {
-                focused_section = Some(section_rc.clone());
+            sections.push(section_rc);
+        }
+    }
+
+    link_tasks(&mut tasks);
+
+    let use_section = sections.len() > 1;
+    let focused_sections = filter_focused_sections(&sections);
+
+    Ok((tasks, use_section, sections, focused_sections))
+}
+
+// The fields of a task that are read from its name
+fn task_from_name(name_without_comment: &str, comment: Option<String>, format: FileFormat) -> Task {
+    let is_focused = text_is_focused(name_without_comment, format);
+    let plain_name = if is_focused {
+        text_remove_focus(name_without_comment, format)
+    } else {
+        name_without_comment.to_string()
+    };
+
+    Task {
+        name: name_without_comment.to_string(),
+        comment,
+        is_focused,
+        due: text_get_due(name_without_comment),
+        tags: text_get_tags(name_without_comment),
+        contexts: text_get_contexts(name_without_comment),
+        priority: text_get_priority(&plain_name),
+        recurrence: text_get_recurrence(name_without_comment),
+        done_date: text_get_done_date(name_without_comment),
+        created: text_get_created_date(name_without_comment),
+        id: text_get_id(&plain_name),
+        blocked_by: text_get_blockers(&plain_name),
+        estimate: text_get_estimate(&plain_name),
+        plain_name,
+        format,
+        ..Default::default()
+    }
+}
+
+// Links children to their parents, and blocked tasks to their blockers
+fn link_tasks(tasks: &mut [Task]) {
+    let parent_links: Vec<(usize, usize)> = tasks
+        .iter()
+        .filter_map(|t| t.parent.map(|parent_num| (parent_num, t.num)))
+        .collect();
+
+    for (parent_num, child_num) in parent_links {
+        tasks[parent_num - 1].children.push(child_num);
+    }
+
+    // a task is blocked as long as one of its blockers is not closed
+    let blocked_nums: Vec<usize> = tasks
+        .iter()
+        .filter(|t| {
+            get_task_blockers(tasks, t)
+                .iter()
+                .any(|b| !state_is_closed(b.state))
+        })
+        .map(|t| t.num)
+        .collect();
+
+    for num in blocked_nums {
+        tasks[num - 1].is_blocked = true;
+    }
+}
+
+// todo.txt: one task per line, with its completion mark, priority and dates
+// in front of the description. The first +project of a task is its section;
+// the focused projects are kept in a "# focus: +project" comment line.
+fn get_todotxt_tasks(content: &str) -> AllTasks {
+    let mut tasks: Vec<Task> = Vec::new();
+    // (project, line num of its first task, line num of its last task)
+    let mut projects: Vec<(String, usize, usize)> = Vec::new();
+    let mut focused_projects: Vec<String> = Vec::new();
+
+    let front_matter_end = text_get_front_matter(content).map_or(0, |(_, end)| end);
+
+    for (index, line) in content.lines().enumerate() {
+        let line_num = index + 1;
+
+        if line_num <= front_matter_end {
+            continue;
+        }
+
+        if line.starts_with('#') {
+            if let Some(cap) = TODOTXT_FOCUS_REGEX.captures(line) {
+                focused_projects.extend(text_get_projects(&cap[1]));
             }
+            continue;
+        }
 
-            sections.push(section_rc);
+        let cap = match TODOTXT_LINE_REGEX.captures(line.trim()) {
+            Some(cap) if !line.trim().is_empty() => cap,
+            _ => continue,
+        };
+
+        let (description, comment) = text_get_comment(&cap[5]);
+        let state = match TODOTXT_STATE_REGEX.captures(&description) {
+            Some(state_cap) => text_get_org_state(&state_cap[1].to_uppercase()),
+            None if cap.get(1).is_some() => TaskState::Done,
+            None => TaskState::Open,
+        };
+        let description = TODOTXT_STATE_REGEX.replace_all(&description, "");
+        let parse_date = |i: usize| {
+            cap.get(i)
+                .and_then(|date| NaiveDate::parse_from_str(date.as_str(), "%Y-%m-%d").ok())
+        };
+
+        let mut task = Task {
+            num: tasks.len() + 1,
+            state,
+            line_num,
+            line_num_end: line_num,
+            line: line.to_string(),
+            ..task_from_name(&description, comment, FileFormat::TodoTxt)
+        };
+        // the priority comes first, the focused description is in bold
+        if let Some(priority) = cap.get(3) {
+            task.name = format!("{} {}", priority.as_str(), task.name);
+            task.plain_name = format!("{} {}", priority.as_str(), task.plain_name);
+            task.priority = text_get_priority(&task.plain_name);
+        }
+        task.done_date = parse_date(2).or(task.done_date);
+        task.created = parse_date(4).or(task.created);
+
+        if let Some(project_cap) = PROJECT_REGEX.captures(&description) {
+            let project = project_cap[1].to_string();
+            match projects.iter_mut().find(|(p, _, _)| *p == project) {
+                Some((_, _, line_num_end)) => *line_num_end = line_num,
+                None => projects.push((project, line_num, line_num)),
+            }
         }
+
+        tasks.push(task);
+    }
+
+    let sections: Vec<Rc<Section>> = projects
+        .into_iter()
+        .enumerate()
+        .map(|(index, (project, line_num, line_num_end))| {
+            Rc::from(Section {
+                num: index + 1,
+                is_focused: focused_projects.contains(&project),
+                name: project.clone(),
+                plain_name: project,
+                line_num,
+                line_num_end,
+                line: String::new(),
+                level: 1,
+                format: FileFormat::TodoTxt,
+                parent: None,
+                depth: 0,
+            })
+        })
+        .collect();
+
+    for task in tasks.iter_mut() {
+        task.section = PROJECT_REGEX
+            .captures(&task.plain_name)
+            .and_then(|cap| sections.iter().find(|s| s.name == cap[1]).cloned());
     }
 
+    link_tasks(&mut tasks);
+
     let use_section = sections.len() > 1;
+    let focused_sections = filter_focused_sections(&sections);
 
-    Ok((tasks, use_section, sections, focused_section))
+    (tasks, use_section, sections, focused_sections)
 }
 
-pub fn get_open_tasks(
-    content_getter: &dyn ContentGetter,
-) -> Result<AllTasks, String> {
-    match get_all_tasks(content_getter) {
-        Ok((tasks, use_sections, sections, focused_section)) => Ok((
-            filter_open_tasks(&tasks, true),
-            use_sections,
-            sections,
-            focused_section,
-        )),
-        Err(msg) => Err(msg),
+// Sets the "# focus: +project +other" line at the top of a todo.txt file, or
+// removes it when no project is focused
+pub fn text_set_todotxt_focus(content: &str, projects: &[String]) -> String {
+    let mut content = text_split_lines(content);
+    content
+        .lines
+        .retain(|(line, _)| !TODOTXT_FOCUS_REGEX.is_match(line));
+
+    if !projects.is_empty() {
+        let focus_line = format!(
+            "# focus: {}",
+            projects
+                .iter()
+                .map(|project| format!("+{}", project))
+                .collect::<Vec<String>>()
+                .join(" ")
+        );
+        content.lines.insert(0, (focus_line, content.newline));
+    }
+
+    text_join_lines(content)
+}
+
+#[derive(Default)]
+struct FileBlocks {
+    // heading line num => (level, line num of the setext underline if any)
+    headings: HashMap<usize, (usize, usize)>,
+    // line nums where a list item starts
+    list_items: HashSet<usize>,
+    // line num => level, of the Org headlines having a TODO keyword
+    headline_tasks: HashMap<usize, usize>,
+}
+
+// Block structure of the document. The files of an aggregated task list are
+// parsed on their own, each one being a top-level section (of level 0)
+// introduced by its marker line.
+fn get_blocks(content: &str, format: FileFormat) -> FileBlocks {
+    let mut blocks = FileBlocks::default();
+
+    let mut add_file_blocks = |file_content: &str, line_num_offset: usize| {
+        let file_blocks = match format {
+            FileFormat::Org => get_org_blocks(file_content),
+            _ => get_markdown_blocks(file_content),
+        };
+
+        for (line_num, (level, line_num_end)) in file_blocks.headings {
+            blocks.headings.insert(
+                line_num + line_num_offset,
+                (level, line_num_end + line_num_offset),
+            );
+        }
+        for line_num in file_blocks.list_items {
+            blocks.list_items.insert(line_num + line_num_offset);
+        }
+        for (line_num, level) in file_blocks.headline_tasks {
+            blocks
+                .headline_tasks
+                .insert(line_num + line_num_offset, level);
+        }
+    };
+
+    let mut file_content = String::new();
+    let mut line_num_offset = 0;
+    let mut marker_line_nums: Vec<usize> = Vec::new();
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        if FILE_MARKER_REGEX.is_match(line.trim_end()) {
+            add_file_blocks(&file_content, line_num_offset);
+            file_content.clear();
+            line_num_offset = index + 1;
+            marker_line_nums.push(index + 1);
+        } else {
+            file_content.push_str(line);
+        }
+    }
+    add_file_blocks(&file_content, line_num_offset);
+
+    for line_num in marker_line_nums {
+        blocks.headings.insert(line_num, (0, line_num));
+    }
+
+    blocks
+}
+
+// Marker line introducing a file of an aggregated task list
+pub fn text_file_marker(file_name: &str) -> String {
+    format!("<!-- tax file: {} -->", file_name)
+}
+
+pub fn text_is_file_marker(line: &str) -> bool {
+    FILE_MARKER_REGEX.is_match(line)
+}
+
+// The section of a file is named after the file, without its extension
+fn text_file_marker_name(file_name: &str) -> &str {
+    match file_name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem,
+        _ => file_name,
+    }
+}
+
+// Block structure of the document, as understood by a CommonMark parser;
+// lines in code blocks, html blocks and front matter belong to no block of
+// interest, and are therefore never parsed as headings or tasks.
+fn get_markdown_blocks(content: &str) -> FileBlocks {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_num_at = |offset: usize| line_starts.partition_point(|start| *start <= offset);
+
+    let mut blocks = FileBlocks::default();
+
+    // headings nested in list items or block quotes are not sections
+    let mut containers_depth = 0;
+
+    let parser = Parser::new_ext(content, Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
+    for (event, range) in parser.into_offset_iter() {
+        match event {
+            Event::Start(Tag::Item) => {
+                blocks.list_items.insert(line_num_at(range.start));
+                containers_depth += 1;
+            }
+            Event::Start(Tag::BlockQuote(_)) => containers_depth += 1,
+            Event::End(TagEnd::Item) | Event::End(TagEnd::BlockQuote(_)) => containers_depth -= 1,
+            Event::Start(Tag::Heading { level, .. }) if containers_depth == 0 => {
+                blocks.headings.insert(
+                    line_num_at(range.start),
+                    (level as usize, line_num_at(range.end - 1)),
+                );
+            }
+            _ => (),
+        }
+    }
+
+    blocks
+}
+
+// Block structure of an Org document: headlines are sections, unless they
+// have a TODO keyword; lines in #+BEGIN_... #+END_... blocks are ignored.
+fn get_org_blocks(content: &str) -> FileBlocks {
+    let mut blocks = FileBlocks::default();
+    let mut in_block = false;
+    let front_matter_end = text_get_front_matter(content).map_or(0, |(_, end)| end);
+
+    for (index, line) in content.lines().enumerate() {
+        let line_num = index + 1;
+
+        if line_num <= front_matter_end {
+            continue;
+        } else if in_block {
+            in_block = !ORG_BLOCK_END_REGEX.is_match(line);
+        } else if ORG_BLOCK_BEGIN_REGEX.is_match(line) {
+            in_block = true;
+        } else if let Some(cap) = ORG_TASK_REGEX.captures(line) {
+            blocks.headline_tasks.insert(line_num, cap[1].len());
+        } else if let Some(cap) = ORG_HEADLINE_REGEX.captures(line) {
+            blocks.headings.insert(line_num, (cap[1].len(), line_num));
+        } else if TASK_LINE_REGEX.is_match(line) {
+            blocks.list_items.insert(line_num);
+        }
+    }
+
+    blocks
+}
+
+// The lines of the YAML front matter opening the content, between --- lines,
+// along with the line num of its closing line
+pub fn text_get_front_matter(content: &str) -> Option<(Vec<&str>, usize)> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let mut lines = content.lines();
+    if lines.next()?.trim_end() != "---" {
+        return None;
     }
+
+    let mut front_matter: Vec<&str> = Vec::new();
+    for line in lines {
+        if line.trim_end() == "---" || line.trim_end() == "..." {
+            let end = front_matter.len() + 2;
+            return Some((front_matter, end));
+        }
+        front_matter.push(line);
+    }
+
+    None
 }
 
-pub fn get_closed_tasks(
+pub fn get_open_tasks(
     content_getter: &dyn ContentGetter,
 ) -> Result<AllTasks, String> {
     match get_all_tasks(content_getter) {
-        Ok((tasks, use_sections, sections, focused_section)) => Ok((
-            filter_open_tasks(&tasks, false),
+        Ok((tasks, use_sections, sections, focused_sections)) => Ok((
+            filter_open_tasks(&tasks, true),
             use_sections,
             sections,
-            focused_section,
+            focused_sections,
         )),
         Err(msg) => Err(msg),
     }
@@ -216,18 +720,61 @@ pub fn filter_focused_tasks(tasks: &[Task], focused: bool) -> Vec<Task> {
         .collect()
 }
 
-pub fn filter_tasks_in_section(tasks: &[Task], section: &Section) -> Vec<Task> {
+// Open tasks due on or before the given date
+pub fn filter_due_tasks(tasks: &[Task], date: NaiveDate) -> Vec<Task> {
+    tasks
+        .iter()
+        .filter(|t| !state_is_closed(t.state) && t.due.is_some_and(|due| due <= date))
+        .cloned()
+        .collect()
+}
+
+// Stable sort, tasks without due date last
+pub fn sort_tasks_by_due(tasks: &[Task]) -> Vec<Task> {
+    let mut sorted_tasks = tasks.to_vec();
+    sorted_tasks.sort_by_key(|t| (t.due.is_none(), t.due));
+    sorted_tasks
+}
+
+// Stable sort, highest priority first, tasks without priority last
+pub fn sort_tasks_by_priority(tasks: &[Task]) -> Vec<Task> {
+    let mut sorted_tasks = tasks.to_vec();
+    sorted_tasks.sort_by_key(|t| (t.priority.is_none(), t.priority));
+    sorted_tasks
+}
+
+pub fn filter_highest_priority_tasks(tasks: &[Task]) -> Vec<Task> {
+    match tasks.iter().filter_map(|t| t.priority).min() {
+        None => tasks.to_vec(),
+        Some(priority) => tasks
+            .iter()
+            .filter(|t| t.priority == Some(priority))
+            .cloned()
+            .collect(),
+    }
+}
+
+pub fn filter_tasks_with_tag(tasks: &[Task], tag: &str) -> Vec<Task> {
+    tasks
+        .iter()
+        .filter(|t| t.tags.iter().any(|task_tag| task_tag.eq_ignore_ascii_case(tag)))
+        .cloned()
+        .collect()
+}
+
+pub fn filter_tasks_with_context(tasks: &[Task], context: &str) -> Vec<Task> {
     tasks
         .iter()
-        .filter(|t| filter_task_in_section_cbk(t, section)).cloned()
+        .filter(|t| t.contexts.iter().any(|c| c.eq_ignore_ascii_case(context)))
+        .cloned()
         .collect()
 }
 
 pub fn filter_open_task_cbk(task: &Task, open: bool) -> bool {
     if open {
-        !task.is_checked
+        !state_is_closed(task.state)
     } else {
-        task.is_checked
+        state_is_closed(task.state)
     }
 }
 
@@ -239,181 +786,1520 @@ pub fn filter_focused_task_cbk(task: &Task, focused: bool) -> bool {
     }
 }
 
+// Tasks of the subsections are in the section too
 pub fn filter_task_in_section_cbk(task: &Task, section: &Section) -> bool {
     match &task.section {
         None => false,
-        Some(s) => s.as_ref().num == section.num,
+        Some(s) => section_is_within(s, section),
     }
 }
 
-pub fn text_is_check_symbol(s: &str) -> bool {
-    s == "x"
-}
-
-pub fn section_to_markdown(section: &Section) -> String {
-    format!(
-        "{} {}",
-        "#".repeat(section.level),
-        if section.is_focused {
-            text_add_focus(&section.plain_name)
-        } else {
-            section.plain_name.clone()
-        },
-    )
-}
-
-pub fn task_to_markdown(task: &Task) -> String {
-    format!(
-        "- [{}] {}{}",
-        if task.is_checked { "x" } else { " " },
-        if task.is_focused {
-            text_add_focus(&task.plain_name)
-        } else {
-            task.plain_name.clone()
-        },
-        if task.comment != None {
-            format!(" // {}", task.comment.clone().unwrap())
-        } else {
-            String::from("")
-        }
-    )
+// Tasks in any of the sections
+pub fn filter_tasks_in_sections(tasks: &[Task], sections: &[Rc<Section>]) -> Vec<Task> {
+    tasks
+        .iter()
+        .filter(|t| sections.iter().any(|s| filter_task_in_section_cbk(t, s)))
+        .cloned()
+        .collect()
 }
 
-pub fn text_add_focus(name: &str) -> String {
-    format!("**{}**", name)
+pub fn filter_focused_sections(sections: &[Rc<Section>]) -> Vec<Rc<Section>> {
+    sections.iter().filter(|s| s.is_focused).cloned().collect()
 }
 
-pub fn text_remove_focus(name: &str) -> String {
-    name.chars().take(name.len() - 2).skip(2).collect()
+// Tasks right under the heading of the section, not in a subsection
+pub fn filter_tasks_in_section_only(tasks: &[Task], section: &Section) -> Vec<Task> {
+    tasks
+        .iter()
+        .filter(|t| t.section.as_ref().is_some_and(|s| s.num == section.num))
+        .cloned()
+        .collect()
 }
 
-pub fn text_is_focused(task_name: &str) -> bool {
-    task_name.len() > 4 && TASK_NAME_FOCUSED_REGEX.is_match(task_name)
+// True if the section is the other section or one of its subsections
+pub fn section_is_within(section: &Section, other: &Section) -> bool {
+    section.num == other.num
+        || section
+            .parent
+            .as_ref()
+            .is_some_and(|parent| section_is_within(parent, other))
 }
 
-pub fn text_get_comment(task_name: &str) -> (String, Option<String>) {
-    match COMMENT_REGEX.captures(task_name) {
-        None => (String::from(task_name), None),
-        Some(cap2) => (cap2[1].trim().to_string(), Some(cap2[2].trim().to_string())),
+// Names of the section and of its parents: Job/Backend
+pub fn section_path(section: &Section) -> String {
+    match &section.parent {
+        Some(parent) => format!("{}/{}", section_path(parent), section.plain_name),
+        None => section.plain_name.clone(),
     }
 }
 
-pub fn text_remove_lines_in_str(s: &str, line_nums: Vec<usize>) -> Result<String, String> {
-    let mut line_num = 1;
-
-    let mut content = String::from("");
+pub fn get_task_descendants(tasks: &[Task], task: &Task) -> Vec<Task> {
+    let mut descendants = Vec::new();
 
-    use std::collections::HashMap;
-    let mut line_nums_hash = HashMap::new();
-
-    for l in &line_nums {
-        line_nums_hash.insert(*l, true);
+    for child_num in &task.children {
+        let child = &tasks[child_num - 1];
+        descendants.push(child.clone());
+        descendants.append(&mut get_task_descendants(tasks, child));
     }
 
-    for line in s.lines() {
-        if !line_nums_hash.contains_key(&line_num) {
-            content += format!("{}\n", line).as_str();
-        }
+    descendants
+}
 
-        line_num += 1;
+pub fn get_task_ancestors(tasks: &[Task], task: &Task) -> Vec<Task> {
+    let mut ancestors = Vec::new();
+    let mut parent = task.parent;
+
+    while let Some(parent_num) = parent {
+        let parent_task = &tasks[parent_num - 1];
+        ancestors.push(parent_task.clone());
+        parent = parent_task.parent;
     }
 
-    Ok(content)
+    ancestors
 }
 
-pub fn text_replace_line_in_str(
-    s: &str,
-    replace_line_num: usize,
-    replacement_line: &str,
-) -> String {
-    let mut line_num = 1;
-
-    let mut content = String::from("");
+// Lines of the task, of its body and of its whole subtree
+pub fn get_task_line_nums(tasks: &[Task], task: &Task) -> Vec<usize> {
+    let mut line_nums: Vec<usize> = (task.line_num..=task.line_num_end).collect();
 
-    for line in s.lines() {
-        if line_num == replace_line_num {
-            content += format!("{}\n", replacement_line).as_str();
-        } else {
-            content += format!("{}\n", line).as_str();
-        }
-
-        line_num += 1;
+    for descendant in get_task_descendants(tasks, task) {
+        line_nums.extend(descendant.line_num..=descendant.line_num_end);
     }
 
-    content
+    // the notes of a task may follow its subtasks
+    line_nums.sort_unstable();
+    line_nums.dedup();
+    line_nums
 }
 
-pub fn text_add_line_in_str(s: &str, add_line_num: usize, added_line: &str) -> String {
-    let mut line_num = 1;
-
-    let mut content = String::from("");
+pub fn text_get_body(task: &Task) -> Vec<String> {
+    let body_indent_width = task
+        .body
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| text_get_indent(l).len())
+        .min()
+        .unwrap_or(0);
 
-    let mut added = false;
+    task.body
+        .iter()
+        .map(|l| l.chars().skip(body_indent_width).collect())
+        .collect()
+}
 
-    for line in s.lines() {
-        if line_num == add_line_num {
-            content += format!("{}\n", added_line).as_str();
-            added = true;
-        }
+pub fn text_get_state(s: &str) -> TaskState {
+    match s {
+        "x" | "X" => TaskState::Done,
+        "-" => TaskState::Cancelled,
+        "/" => TaskState::InProgress,
+        ">" => TaskState::Deferred,
+        _ => TaskState::Open,
+    }
+}
 
-        content += format!("{}\n", line).as_str();
-        line_num += 1;
+pub fn text_get_org_state(keyword: &str) -> TaskState {
+    match keyword {
+        "DONE" => TaskState::Done,
+        "CANCELLED" | "CANCELED" => TaskState::Cancelled,
+        "STARTED" => TaskState::InProgress,
+        "WAITING" => TaskState::Deferred,
+        _ => TaskState::Open,
     }
+}
 
-    if !added {
-        // either empty file or line past end of file
-        content += format!("{}\n", added_line).as_str();
+pub fn state_to_org_keyword(state: TaskState) -> &'static str {
+    match state {
+        TaskState::Open => "TODO",
+        TaskState::Done => "DONE",
+        TaskState::Cancelled => "CANCELLED",
+        TaskState::InProgress => "STARTED",
+        TaskState::Deferred => "WAITING",
     }
+}
 
-    content
+// DEADLINE: <2020-06-15 Mon>, or else SCHEDULED: <...>
+pub fn text_get_org_planning_due(line: &str) -> Option<NaiveDate> {
+    let dates: Vec<(String, NaiveDate)> = ORG_PLANNING_REGEX
+        .captures_iter(line)
+        .filter_map(|cap| {
+            NaiveDate::parse_from_str(&cap[2], "%Y-%m-%d")
+                .ok()
+                .map(|date| (cap[1].to_string(), date))
+        })
+        .collect();
+
+    dates
+        .iter()
+        .find(|(keyword, _)| keyword == "DEADLINE")
+        .or_else(|| dates.first())
+        .map(|(_, date)| *date)
 }
 
-pub fn search_section(search: &str, sections: &[Rc<Section>]) -> Option<Section> {
-    let section_name_lower = search.to_lowercase();
+pub fn state_to_symbol(state: TaskState) -> &'static str {
+    match state {
+        TaskState::Open => " ",
+        TaskState::Done => "x",
+        TaskState::Cancelled => "-",
+        TaskState::InProgress => "/",
+        TaskState::Deferred => ">",
+    }
+}
 
-    let mut partial_match: Option<Section> = None;
-    let mut exact_match: Option<Section> = None;
+// Done and cancelled tasks are closed; the others are open
+pub fn state_is_closed(state: TaskState) -> bool {
+    state == TaskState::Done || state == TaskState::Cancelled
+}
 
-    for section_rc in sections {
-        let section_cur = section_rc.as_ref();
-        let section_cur_name_lower = section_cur.name.to_lowercase();
+pub fn section_to_line(section: &Section) -> String {
+    // the marker line of a file is never changed
+    if section.level == 0 {
+        return section.line.clone();
+    }
 
-        if section_cur_name_lower == section_name_lower {
-            exact_match = Some(section_cur.clone());
-            break;
-        } else if section_cur_name_lower.contains(&section_name_lower) {
-            partial_match = Some(section_cur.clone());
-        }
+    let name = if section.is_focused {
+        text_add_focus(&section.plain_name, section.format)
+    } else {
+        section.plain_name.clone()
+    };
+
+    // only the name is replaced in the heading line, which keeps its spacing
+    let heading_char = if section.format == FileFormat::Org {
+        '*'
+    } else {
+        '#'
+    };
+    let markup_len = section.line.len()
+        - section
+            .line
+            .trim_start()
+            .trim_start_matches(heading_char)
+            .trim_start()
+            .len();
+    if section.line[markup_len..].starts_with(&section.name) {
+        return format!(
+            "{}{}{}",
+            &section.line[..markup_len],
+            name,
+            &section.line[markup_len + section.name.len()..]
+        );
     }
 
-    match exact_match {
-        None => partial_match,
-        _ => exact_match,
+    if section.format == FileFormat::Org {
+        format!("{} {}", "*".repeat(section.level), name)
+    } else if section.line.trim_start().starts_with('#') {
+        format!("{} {}", "#".repeat(section.level), name)
+    } else {
+        // setext heading: the underline is left untouched
+        name
     }
 }
 
-#[cfg(test)]
-mod tests {
-
-    use super::*;
-    use crate::test_helpers::test::{get_std_test_contents, get_std_test_tasks, ContentGetterMock};
-
-    #[test]
-    fn test_task_to_markdown() {
-        let (expected_markdown, tasks) = get_std_test_tasks();
-        let lines: Vec<&str> = expected_markdown.lines().collect();
+// The line of the task, in the syntax of its file
+pub fn task_to_line(task: &Task) -> String {
+    if let Some(line) = text_update_task_line(task) {
+        return line;
+    }
 
-        for (i, task) in tasks.into_iter().enumerate() {
-            assert_eq!(lines[i], task_to_markdown(&task));
+    let name = format!(
+        "{}{}",
+        if task.is_focused {
+            text_add_focus(&task.plain_name, task.format)
+        } else {
+            task.plain_name.clone()
+        },
+        if task.comment.is_some() {
+            format!(" // {}", task.comment.clone().unwrap())
+        } else {
+            String::from("")
+        }
+    );
+
+    match (task.format, task.org_level) {
+        (FileFormat::TodoTxt, _) => todotxt_task_to_line(task),
+        (FileFormat::Org, Some(level)) => format!(
+            "{} {} {}",
+            "*".repeat(level),
+            state_to_org_keyword(task.state),
+            name
+        ),
+        // Org checkboxes are checked with an uppercase X
+        (FileFormat::Org, None) if task.state == TaskState::Done => {
+            format!("{}- [X] {}", task.indent, name)
         }
+        _ => format!(
+            "{}- [{}] {}",
+            task.indent,
+            state_to_symbol(task.state),
+            name
+        ),
     }
+}
 
-    #[test]
-    fn test_is_check_symbol() {
-        assert!(!text_is_check_symbol(""));
-        assert!(!text_is_check_symbol("*"));
-        assert!(text_is_check_symbol("x"));
-        assert!(!text_is_check_symbol("X"));
+// The line of the task rewritten in place: only the state and the name are
+// replaced, so that bullets, numbering, spacing and comments are left as is
+fn text_update_task_line(task: &Task) -> Option<String> {
+    let line = task.line.as_str();
+    let (state_range, state_changed, state_text, name_range) = match task.org_level {
+        _ if task.format == FileFormat::TodoTxt => return None,
+        Some(_) => {
+            let cap = ORG_TASK_REGEX.captures(line)?;
+            (
+                cap.get(2)?.range(),
+                text_get_org_state(&cap[2]) != task.state,
+                state_to_org_keyword(task.state),
+                cap.get(3)?.range(),
+            )
+        }
+        None => {
+            let cap = TASK_LINE_REGEX.captures(line)?;
+            (
+                cap.get(2)?.range(),
+                text_get_state(cap[2].trim()) != task.state,
+                // Org checkboxes are checked with an uppercase X
+                match (task.format, task.state) {
+                    (FileFormat::Org, TaskState::Done) => "X",
+                    (_, state) => state_to_symbol(state),
+                },
+                cap.get(3)?.range(),
+            )
+        }
+    };
+
+    let name_text = &line[name_range.clone()];
+    let (name, comment) = text_get_comment(name_text.trim());
+    if !name_text.starts_with(&name) {
+        return None;
+    }
+
+    let new_name = if task.is_focused {
+        text_add_focus(&task.plain_name, task.format)
+    } else {
+        task.plain_name.clone()
+    };
+    let new_comment = if comment == task.comment {
+        name_text[name.len()..].to_string()
+    } else {
+        match &task.comment {
+            Some(comment) => format!(" // {}", comment),
+            None => String::new(),
+        }
+    };
+
+    Some(format!(
+        "{}{}{}{}{}{}",
+        &line[..state_range.start],
+        if state_changed {
+            state_text
+        } else {
+            &line[state_range.clone()]
+        },
+        &line[state_range.end..name_range.start],
+        new_name,
+        new_comment,
+        &line[name_range.end..]
+    ))
+}
+
+// x 2020-06-15 (A) 2020-06-01 Description +project state:started // comment
+fn todotxt_task_to_line(task: &Task) -> String {
+    let (priority, description) = match PRIORITY_REGEX.captures(&task.plain_name) {
+        Some(cap) if cap.get(1).is_some() => (
+            Some(cap[1].to_string()),
+            task.plain_name[cap.get(0).unwrap().end()..].trim_start(),
+        ),
+        _ => (None, task.plain_name.as_str()),
+    };
+
+    let mut parts: Vec<String> = Vec::new();
+    if state_is_closed(task.state) {
+        parts.push(String::from("x"));
+        // dates written as done: or created: stamps stay in the description
+        if let Some(done_date) = task
+            .done_date
+            .filter(|_| text_get_done_date(description).is_none())
+        {
+            parts.push(done_date.format("%Y-%m-%d").to_string());
+        }
+    }
+    if let Some(priority) = priority {
+        parts.push(format!("({})", priority));
+    }
+    if let Some(created) = task
+        .created
+        .filter(|_| text_get_created_date(description).is_none())
+    {
+        parts.push(created.format("%Y-%m-%d").to_string());
+    }
+    parts.push(if task.is_focused {
+        text_add_focus(description, task.format)
+    } else {
+        description.to_string()
+    });
+    match task.state {
+        TaskState::InProgress => parts.push(String::from("state:started")),
+        TaskState::Deferred => parts.push(String::from("state:waiting")),
+        TaskState::Cancelled => parts.push(String::from("state:cancelled")),
+        _ => (),
+    }
+    if let Some(comment) = &task.comment {
+        parts.push(format!("// {}", comment));
+    }
+
+    parts.join(" ")
+}
+
+// Focused names are in bold: **name** in Markdown and todo.txt, *name* in Org
+pub fn text_add_focus(name: &str, format: FileFormat) -> String {
+    let (prefix, title, metadata) = text_split_title(name, format);
+    match format {
+        FileFormat::Markdown | FileFormat::TodoTxt => {
+            format!("{}**{}**{}", prefix, title, metadata)
+        }
+        FileFormat::Org => format!("{}*{}*{}", prefix, title, metadata),
+    }
+}
+
+pub fn text_remove_focus(name: &str, format: FileFormat) -> String {
+    let (prefix, title, metadata) = text_split_title(name, format);
+    let markup_len = match format {
+        FileFormat::Markdown | FileFormat::TodoTxt => 2,
+        FileFormat::Org => 1,
+    };
+    let title: String = title
+        .chars()
+        .take(title.chars().count() - markup_len)
+        .skip(markup_len)
+        .collect();
+    format!("{}{}{}", prefix, title, metadata)
+}
+
+pub fn text_is_focused(task_name: &str, format: FileFormat) -> bool {
+    let (_, title, _) = text_split_title(task_name, format);
+    match format {
+        FileFormat::Markdown | FileFormat::TodoTxt => {
+            title.len() > 4 && TASK_NAME_FOCUSED_REGEX.is_match(title)
+        }
+        FileFormat::Org => ORG_FOCUSED_REGEX.is_match(title),
+    }
+}
+
+// The name split around its title, the part in the focus markup: the priority
+// cookie of an Org headline comes before it, and the trailing metadata tokens,
+// like an ^id, a created: stamp, Org :tags: or todo.txt +projects and
+// @contexts, come after it
+fn text_split_title(name: &str, format: FileFormat) -> (&str, &str, &str) {
+    let title_start = match PRIORITY_REGEX.captures(name) {
+        Some(cap) if format == FileFormat::Org && cap.get(3).is_some() => {
+            let rest = name[cap.get(0).unwrap().end()..].trim_start();
+            Some(name.len() - rest.len()).filter(|_| !rest.is_empty())
+        }
+        _ => None,
+    }
+    .unwrap_or(0);
+
+    let mut title_end = name.trim_end().len();
+    while let Some(pos) = name[title_start..title_end].rfind(char::is_whitespace) {
+        let pos = title_start + pos;
+        let token = name[pos..title_end].trim_start();
+        let is_metadata = METADATA_TOKEN_REGEX.is_match(token)
+            || match format {
+                FileFormat::Org => ORG_TAGS_REGEX.is_match(token),
+                FileFormat::TodoTxt => {
+                    token.len() > 1 && (token.starts_with('+') || token.starts_with('@'))
+                }
+                FileFormat::Markdown => false,
+            };
+        if token.ends_with('*') || !is_metadata {
+            break;
+        }
+        title_end = name[..pos].trim_end().len();
+    }
+
+    (
+        &name[..title_start],
+        &name[title_start..title_end],
+        &name[title_end..],
+    )
+}
+
+pub fn text_get_indent(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+pub fn text_indent_width(indent: &str) -> usize {
+    indent
+        .chars()
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+pub fn text_get_due(task_name: &str) -> Option<NaiveDate> {
+    DUE_REGEX
+        .captures(task_name)
+        .and_then(|cap| NaiveDate::parse_from_str(&cap[1], "%Y-%m-%d").ok())
+}
+
+// (A) is the highest priority; !!!, !! and ! stand for (A), (B) and (C)
+pub fn text_get_priority(task_name: &str) -> Option<char> {
+    text_get_priority_markup(task_name).map(|markup| match markup.as_str() {
+        "!!!" => 'A',
+        "!!" => 'B',
+        "!" => 'C',
+        _ => markup.chars().find(|c| c.is_ascii_uppercase()).unwrap(),
+    })
+}
+
+pub fn text_get_priority_markup(task_name: &str) -> Option<String> {
+    PRIORITY_REGEX
+        .captures(task_name)
+        .map(|cap| match (cap.get(1), cap.get(3)) {
+            (Some(letter), _) => format!("({})", letter.as_str()),
+            (None, Some(letter)) => format!("[#{}]", letter.as_str()),
+            _ => cap[2].to_string(),
+        })
+}
+
+// #tags, and Org :tags:at:the:end:
+pub fn text_get_tags(task_name: &str) -> Vec<String> {
+    let mut tags: Vec<String> = TAG_REGEX
+        .captures_iter(task_name)
+        .map(|cap| cap[1].to_string())
+        .collect();
+
+    if let Some(cap) = ORG_TAGS_REGEX.captures(task_name) {
+        tags.extend(
+            cap[1]
+                .split(':')
+                .filter(|tag| !tag.is_empty())
+                .map(String::from),
+        );
+    }
+
+    tags
+}
+
+pub fn text_get_contexts(task_name: &str) -> Vec<String> {
+    CONTEXT_REGEX
+        .captures_iter(task_name)
+        .map(|cap| cap[1].to_string())
+        .collect()
+}
+
+// todo.txt +projects
+pub fn text_get_projects(task_name: &str) -> Vec<String> {
+    PROJECT_REGEX
+        .captures_iter(task_name)
+        .map(|cap| cap[1].to_string())
+        .collect()
+}
+
+pub fn text_set_due(task_name: &str, due: NaiveDate) -> String {
+    let due_markup = format!("due:{}", due.format("%Y-%m-%d"));
+
+    match DUE_REGEX.captures(task_name) {
+        Some(cap) => {
+            let date = cap.get(1).unwrap();
+            format!(
+                "{}{}{}",
+                &task_name[..date.start() - "due:".len()],
+                due_markup,
+                &task_name[date.end()..]
+            )
+        }
+        None => format!("{} {}", task_name, due_markup),
+    }
+}
+
+pub fn text_get_done_date(task_name: &str) -> Option<NaiveDate> {
+    DONE_REGEX
+        .captures(task_name)
+        .and_then(|cap| NaiveDate::parse_from_str(&cap[1], "%Y-%m-%d").ok())
+}
+
+// Replaces the done:... stamp of the task name, or removes it if None
+pub fn text_set_done(task_name: &str, done_stamp: Option<&str>) -> String {
+    text_set_stamp(task_name, &DONE_REGEX, "done", done_stamp)
+}
+
+pub fn text_get_created_date(task_name: &str) -> Option<NaiveDate> {
+    CREATED_REGEX
+        .captures(task_name)
+        .and_then(|cap| NaiveDate::parse_from_str(&cap[1], "%Y-%m-%d").ok())
+}
+
+// Replaces the created:... stamp of the task name, or removes it if None
+pub fn text_set_created(task_name: &str, created_stamp: Option<&str>) -> String {
+    text_set_stamp(task_name, &CREATED_REGEX, "created", created_stamp)
+}
+
+// Stamps are written before the trailing ^id of the task, if any
+fn text_set_stamp(task_name: &str, regex: &Regex, key: &str, stamp: Option<&str>) -> String {
+    let id = text_get_id(task_name);
+    let name_without_id = ID_REGEX.replace(task_name, "");
+    let name_without_stamp = regex.replace_all(&name_without_id, "").to_string();
+
+    let name = match stamp {
+        Some(stamp) => format!("{} {}:{}", name_without_stamp, key, stamp),
+        None => name_without_stamp,
+    };
+
+    match id {
+        Some(id) => format!("{} ^{}", name, id),
+        None => name,
+    }
+}
+
+// Ids of the tasks the task is blocked by (after:^id)
+pub fn text_get_blockers(task_name: &str) -> Vec<String> {
+    AFTER_REGEX
+        .captures_iter(task_name)
+        .map(|cap| String::from(&cap[1]))
+        .collect()
+}
+
+// Tasks the given task is blocked by; unknown ids are ignored
+pub fn get_task_blockers(tasks: &[Task], task: &Task) -> Vec<Task> {
+    tasks
+        .iter()
+        .filter(|t| match &t.id {
+            Some(id) => task.blocked_by.contains(id),
+            None => false,
+        })
+        .cloned()
+        .collect()
+}
+
+pub fn text_get_id(task_name: &str) -> Option<String> {
+    ID_REGEX
+        .captures(task_name.trim_end())
+        .map(|cap| String::from(&cap[1]))
+}
+
+// Replaces the trailing ^id of the task name, or adds one
+pub fn text_set_id(task_name: &str, id: &str) -> String {
+    match ID_REGEX.captures(task_name.trim_end()) {
+        Some(cap) => format!("{}{}", &task_name[..cap.get(1).unwrap().start()], id),
+        None => format!("{} ^{}", task_name, id),
+    }
+}
+
+// Short id, not used by any of the given tasks, derived from the seed
+pub fn get_new_task_id(tasks: &[Task], seed: u64) -> String {
+    let mut attempt: u64 = 0;
+    loop {
+        // splitmix64, to spread close seeds apart
+        let mut hash = seed.wrapping_add((attempt + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        hash ^= hash >> 31;
+
+        // 4 chars, then longer ids if the short ones keep colliding
+        let len = 4 + (attempt / 16) as usize;
+        let mut id = String::new();
+        for _ in 0..len {
+            id.push(std::char::from_digit((hash % 36) as u32, 36).unwrap());
+            hash /= 36;
+        }
+
+        // an id made of digits only would be taken for a task number
+        let has_letter = id.chars().any(|c| c.is_ascii_alphabetic());
+        if has_letter && !tasks.iter().any(|t| t.id.as_ref() == Some(&id)) {
+            return id;
+        }
+        attempt += 1;
+    }
+}
+
+// Number of the task designated by its id (with or without ^), or by its number
+pub fn get_task_num_by_ref(tasks: &[Task], task_ref: &str) -> Option<usize> {
+    let id = task_ref.trim_start_matches('^');
+    if let Some(task) = tasks.iter().find(|t| t.id.as_deref() == Some(id)) {
+        return Some(task.num);
+    }
+
+    if task_ref.starts_with('^') {
+        return None;
+    }
+    task_ref.parse::<usize>().ok()
+}
+
+pub fn get_task_num(content_getter: &dyn ContentGetter, task_ref: &str) -> Result<usize, String> {
+    let (tasks, _, _, _) = get_all_tasks(content_getter)?;
+    match get_task_num_by_ref(&tasks, task_ref) {
+        Some(num) => Ok(num),
+        None => Err(format!("Non existent task {}", task_ref)),
+    }
+}
+
+// Human readable age of something created at the given date, eg "3 months"
+pub fn text_format_age(created: NaiveDate, today: NaiveDate) -> String {
+    let days = (today - created).num_days().max(0);
+    let (count, unit) = if days < 14 {
+        (days, "day")
+    } else if days < 60 {
+        (days / 7, "week")
+    } else if days < 730 {
+        (days / 30, "month")
+    } else {
+        (days / 365, "year")
+    };
+
+    format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+}
+
+// Estimate in minutes, written ~2h, ~30m or ~1h30m
+pub fn text_get_estimate(task_name: &str) -> Option<i64> {
+    ESTIMATE_REGEX
+        .captures_iter(task_name)
+        .find_map(|cap| match (cap.get(1), cap.get(2)) {
+            (None, None) => None,
+            (hours, minutes) => {
+                let parse = |m: Option<regex::Match>| {
+                    m.map_or(0, |m| m.as_str().parse::<i64>().unwrap_or(0))
+                };
+                Some(parse(hours) * 60 + parse(minutes))
+            }
+        })
+}
+
+// Duration in minutes, as 1h30m, 2h or 45m
+pub fn text_format_duration(minutes: i64) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{}m", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h{}m", h, m),
+    }
+}
+
+// Time log lines are "task_id start [end]"
+pub fn get_time_intervals(timelog_getter: &dyn ContentGetter) -> Result<Vec<TimeInterval>, String> {
+    let parse_time = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S").ok();
+
+    let mut intervals: Vec<TimeInterval> = Vec::new();
+    for (index, line) in timelog_getter.get_contents()?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let interval = match parts.as_slice() {
+            [task_id, start] => parse_time(start).map(|start| TimeInterval {
+                task_id: task_id.to_string(),
+                start,
+                end: None,
+            }),
+            [task_id, start, end] => match (parse_time(start), parse_time(end)) {
+                (Some(start), Some(end)) => Some(TimeInterval {
+                    task_id: task_id.to_string(),
+                    start,
+                    end: Some(end),
+                }),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        match interval {
+            Some(interval) => intervals.push(interval),
+            None => return Err(format!("Invalid time log line {}: {}", index + 1, line)),
+        }
+    }
+
+    Ok(intervals)
+}
+
+pub fn time_intervals_to_string(intervals: &[TimeInterval]) -> String {
+    intervals
+        .iter()
+        .map(|interval| {
+            let start = interval.start.format("%Y-%m-%dT%H:%M:%S");
+            match interval.end {
+                Some(end) => format!(
+                    "{} {} {}\n",
+                    interval.task_id,
+                    start,
+                    end.format("%Y-%m-%dT%H:%M:%S")
+                ),
+                None => format!("{} {}\n", interval.task_id, start),
+            }
+        })
+        .collect()
+}
+
+// Duration of the interval in minutes; running intervals end now
+pub fn get_interval_minutes(interval: &TimeInterval, now: NaiveDateTime) -> i64 {
+    (interval.end.unwrap_or(now) - interval.start)
+        .num_minutes()
+        .max(0)
+}
+
+// Date given as YYYY-MM-DD, today, yesterday, or a weekday name (the last
+// such day, today included)
+pub fn text_parse_since(since: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    let since = since.trim().to_lowercase();
+
+    if let Ok(date) = NaiveDate::parse_from_str(&since, "%Y-%m-%d") {
+        return Ok(date);
+    }
+
+    match since.as_str() {
+        "today" => return Ok(today),
+        "yesterday" => return Ok(today - Days::new(1)),
+        _ => (),
+    };
+
+    match since.parse::<Weekday>() {
+        Ok(weekday) => {
+            let days_back =
+                (7 + today.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+            Ok(today - Days::new(days_back as u64))
+        }
+        Err(_) => Err(format!("Invalid date: {}", since)),
+    }
+}
+
+pub fn text_format_stamp(stamp: Stamp, now: NaiveDateTime) -> Option<String> {
+    match stamp {
+        Stamp::None => None,
+        Stamp::Date => Some(now.format("%Y-%m-%d").to_string()),
+        Stamp::DateTime => Some(now.format("%Y-%m-%dT%H:%M").to_string()),
+    }
+}
+
+pub fn text_get_recurrence(task_name: &str) -> Option<Recurrence> {
+    RECURRENCE_REGEX
+        .captures(task_name)
+        .and_then(|cap| match &cap[1] {
+            "day" => Some(Recurrence::Daily),
+            "weekday" => Some(Recurrence::Weekdays),
+            "week" => Some(Recurrence::Weekly),
+            "month" => Some(Recurrence::Monthly),
+            "year" => Some(Recurrence::Yearly),
+            _ => match cap[2].parse::<u32>() {
+                Ok(day) if (1..=31).contains(&day) => Some(Recurrence::MonthDay(day)),
+                _ => None,
+            },
+        })
+}
+
+// Next occurrence strictly after today, following the schedule from the
+// current due date if any
+pub fn get_next_due(
+    recurrence: &Recurrence,
+    due: Option<NaiveDate>,
+    today: NaiveDate,
+) -> NaiveDate {
+    let start = due.unwrap_or(today);
+    let mut next = start;
+    // months are counted from the start, so that the 31st comes back after
+    // a shorter month
+    let mut count = 0;
+
+    loop {
+        count += 1;
+        next = match recurrence {
+            Recurrence::Daily => next + Days::new(1),
+            Recurrence::Weekdays => match next.weekday() {
+                Weekday::Fri => next + Days::new(3),
+                Weekday::Sat => next + Days::new(2),
+                _ => next + Days::new(1),
+            },
+            Recurrence::Weekly => next + Days::new(7),
+            Recurrence::Monthly => start + Months::new(count),
+            Recurrence::Yearly => start + Months::new(12 * count),
+            Recurrence::MonthDay(day) => {
+                let next_month = next.with_day(1).unwrap() + Months::new(1);
+                match date_with_day_clamped(next, *day) {
+                    date if date > next => date,
+                    _ => date_with_day_clamped(next_month, *day),
+                }
+            }
+        };
+
+        if next > today {
+            return next;
+        }
+    }
+}
+
+// The given day of the month of the date, or the last day of a shorter month
+fn date_with_day_clamped(date: NaiveDate, day: u32) -> NaiveDate {
+    date.with_day(day)
+        .unwrap_or_else(|| date.with_day(1).unwrap() + Months::new(1) - Days::new(1))
+}
+
+pub fn text_get_comment(task_name: &str) -> (String, Option<String>) {
+    match COMMENT_REGEX.captures(task_name) {
+        None => (String::from(task_name), None),
+        Some(cap2) => (cap2[1].trim().to_string(), Some(cap2[2].trim().to_string())),
+    }
+}
+
+// The lines of the content with their own line ending, so that editing a
+// line keeps the line endings, the final newline and the BOM of the file
+struct ContentLines {
+    bom: bool,
+    // line ending of the added lines: the first one of the file, or \n
+    newline: &'static str,
+    lines: Vec<(String, &'static str)>,
+}
+
+fn text_split_lines(s: &str) -> ContentLines {
+    let (bom, s) = match s.strip_prefix('\u{feff}') {
+        Some(without_bom) => (true, without_bom),
+        None => (false, s),
+    };
+
+    let lines: Vec<(String, &'static str)> = s
+        .split_inclusive('\n')
+        .map(|line| match line.strip_suffix("\r\n") {
+            Some(line) => (line.to_string(), "\r\n"),
+            None => match line.strip_suffix('\n') {
+                Some(line) => (line.to_string(), "\n"),
+                None => (line.to_string(), ""),
+            },
+        })
+        .collect();
+
+    ContentLines {
+        bom,
+        newline: match lines.first() {
+            Some((_, "\r\n")) => "\r\n",
+            _ => "\n",
+        },
+        lines,
+    }
+}
+
+fn text_join_lines(content: ContentLines) -> String {
+    let mut s = String::from(if content.bom { "\u{feff}" } else { "" });
+    for (line, ending) in content.lines {
+        s.push_str(&line);
+        s.push_str(ending);
+    }
+    s
+}
+
+pub fn text_remove_lines_in_str(s: &str, line_nums: Vec<usize>) -> Result<String, String> {
+    let mut content = text_split_lines(s);
+    let final_ending = content.lines.last().map(|(_, ending)| *ending);
+
+    content.lines = content
+        .lines
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !line_nums.contains(&(i + 1)))
+        .map(|(_, line)| line)
+        .collect();
+
+    // the new last line ends like the removed one
+    if let (Some((_, ending)), Some(final_ending)) = (content.lines.last_mut(), final_ending) {
+        *ending = final_ending;
+    }
+
+    Ok(text_join_lines(content))
+}
+
+pub fn text_replace_line_in_str(
+    s: &str,
+    replace_line_num: usize,
+    replacement_line: &str,
+) -> String {
+    let mut content = text_split_lines(s);
+    let newline = content.newline;
+
+    if let Some((line, _)) = content.lines.get_mut(replace_line_num.wrapping_sub(1)) {
+        *line = replacement_line.replace('\n', newline);
+    }
+
+    text_join_lines(content)
+}
+
+pub fn text_add_line_in_str(s: &str, add_line_num: usize, added_line: &str) -> String {
+    let mut content = text_split_lines(s);
+    let added_line = added_line.replace('\n', content.newline);
+
+    if add_line_num >= 1 && add_line_num <= content.lines.len() {
+        content
+            .lines
+            .insert(add_line_num - 1, (added_line, content.newline));
+    } else {
+        // either empty file or line past end of file: the added line becomes
+        // the last one, with or without a final newline like the previous one
+        let ending = match content.lines.last_mut() {
+            Some((_, ending)) => std::mem::replace(ending, content.newline),
+            None => content.newline,
+        };
+        content.lines.push((added_line, ending));
+    }
+
+    text_join_lines(content)
+}
+
+pub fn search_section(search: &str, sections: &[Rc<Section>]) -> Option<Section> {
+    let section_name_lower = search.to_lowercase();
+
+    let mut partial_match: Option<Section> = None;
+    let mut exact_match: Option<Section> = None;
+
+    for section_rc in sections {
+        let section_cur = section_rc.as_ref();
+        let section_cur_name_lower = section_cur.name.to_lowercase();
+        // Job/Backend designates the Backend subsection of Job
+        let section_cur_path_lower = section_path(section_cur).to_lowercase();
+
+        if section_cur_name_lower == section_name_lower
+            || section_cur_path_lower == section_name_lower
+        {
+            exact_match = Some(section_cur.clone());
+            break;
+        } else if section_cur_name_lower.contains(&section_name_lower)
+            || section_cur_path_lower.contains(&section_name_lower)
+        {
+            partial_match = Some(section_cur.clone());
+        }
+    }
+
+    match exact_match {
+        None => partial_match,
+        _ => exact_match,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::test_helpers::test::{
+        get_std_test_contents, get_std_test_tasks, now_getter_fixed, today_getter_fixed,
+        ContentGetterMock,
+    };
+
+    #[test]
+    fn test_task_to_line() {
+        let (expected_markdown, tasks) = get_std_test_tasks();
+        let lines: Vec<&str> = expected_markdown.lines().collect();
+
+        for (i, task) in tasks.into_iter().enumerate() {
+            assert_eq!(lines[i], task_to_line(&task));
+        }
+    }
+
+    #[test]
+    fn test_task_to_line_in_place() {
+        let content_getter = ContentGetterMock::new(Ok([
+            "##  Job  ",
+            "* [ ] Send the invoice   //   by friday",
+            "1.  [x]  Fix the build\t",
+            "\t+ [] **Bump the compiler**",
+        ]
+        .join("\n")));
+        let (tasks, _, sections, _) = get_all_tasks(&content_getter).unwrap();
+
+        let mut task = tasks[0].clone();
+        task.state = TaskState::Done;
+        assert_eq!(
+            task_to_line(&task),
+            "* [x] Send the invoice   //   by friday"
+        );
+        task.is_focused = true;
+        assert_eq!(
+            task_to_line(&task),
+            "* [x] **Send the invoice**   //   by friday"
+        );
+        task.comment = None;
+        assert_eq!(task_to_line(&task), "* [x] **Send the invoice**");
+
+        let mut task = tasks[1].clone();
+        task.state = TaskState::Open;
+        assert_eq!(task_to_line(&task), "1.  [ ]  Fix the build\t");
+
+        let mut task = tasks[2].clone();
+        assert_eq!(task_to_line(&task), "\t+ [] **Bump the compiler**");
+        task.is_focused = false;
+        assert_eq!(task_to_line(&task), "\t+ [] Bump the compiler");
+
+        let mut section = sections[0].as_ref().clone();
+        section.is_focused = true;
+        assert_eq!(section_to_line(&section), "##  **Job**  ");
+    }
+
+    #[test]
+    fn test_text_get_due() {
+        assert_eq!(
+            text_get_due("Pay the bills due:2020-06-30"),
+            NaiveDate::from_ymd_opt(2020, 6, 30)
+        );
+        assert_eq!(text_get_due("due:2020-06-30 Pay the bills"), NaiveDate::from_ymd_opt(2020, 6, 30));
+        assert_eq!(text_get_due("Pay the bills"), None);
+        assert_eq!(text_get_due("Pay the bills overdue:2020-06-30"), None);
+        assert_eq!(text_get_due("Pay the bills due:2020-13-45"), None);
+    }
+
+    #[test]
+    fn test_text_get_tags_and_contexts() {
+        let name = "#backend Fix issue #555 see http://example.com/#anchor @phone #api-v2 mail@example.com";
+        assert_eq!(text_get_tags(name), vec!["backend", "api-v2"]);
+        assert_eq!(text_get_contexts(name), vec!["phone"]);
+    }
+
+    #[test]
+    fn test_text_get_priority() {
+        assert_eq!(text_get_priority("(A) Call mom"), Some('A'));
+        assert_eq!(text_get_priority("(C)"), Some('C'));
+        assert_eq!(text_get_priority("Call mom (A)"), None);
+        assert_eq!(text_get_priority("(a) Call mom"), None);
+        assert_eq!(text_get_priority("Call mom !!!"), Some('A'));
+        assert_eq!(text_get_priority("!! Call mom"), Some('B'));
+        assert_eq!(text_get_priority("Call ! mom"), Some('C'));
+        assert_eq!(text_get_priority("Call mom!"), None);
+        assert_eq!(text_get_priority("Call mom !!!!"), None);
+        assert_eq!(text_get_priority_markup("Call mom !!"), Some(String::from("!!")));
+
+        // the priority of a focused task is inside the focus markup
+        let content_getter = ContentGetterMock::new(Ok(String::from("- [ ] **(A) Call mom**")));
+        let (tasks, _, _, _) = get_all_tasks(&content_getter).unwrap();
+        assert_eq!(tasks[0].priority, Some('A'));
+    }
+
+    #[test]
+    fn test_text_get_recurrence() {
+        assert_eq!(
+            text_get_recurrence("Take out the trash every:week"),
+            Some(Recurrence::Weekly)
+        );
+        assert_eq!(
+            text_get_recurrence("every:weekday Standup"),
+            Some(Recurrence::Weekdays)
+        );
+        assert_eq!(
+            text_get_recurrence("Pay the rent every:monthday 1 due:2020-06-01"),
+            Some(Recurrence::MonthDay(1))
+        );
+        assert_eq!(text_get_recurrence("Pay the rent every:monthday 32"), None);
+        assert_eq!(text_get_recurrence("Pay the rent every:fortnight"), None);
+    }
+
+    #[test]
+    fn test_get_next_due() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let today = today_getter_fixed(); // monday 2020-06-15
+
+        assert_eq!(
+            get_next_due(&Recurrence::Daily, None, today),
+            date(2020, 6, 16)
+        );
+        assert_eq!(
+            get_next_due(&Recurrence::Weekly, Some(date(2020, 6, 17)), today),
+            date(2020, 6, 24)
+        );
+        assert_eq!(
+            get_next_due(&Recurrence::Weekly, Some(date(2020, 6, 1)), today),
+            date(2020, 6, 22)
+        );
+        assert_eq!(
+            get_next_due(&Recurrence::Weekdays, Some(date(2020, 6, 19)), today),
+            date(2020, 6, 22)
+        );
+        assert_eq!(
+            get_next_due(&Recurrence::Monthly, Some(date(2020, 1, 31)), today),
+            date(2020, 6, 30)
+        );
+        assert_eq!(
+            get_next_due(&Recurrence::Yearly, Some(date(2016, 2, 29)), today),
+            date(2021, 2, 28)
+        );
+        assert_eq!(
+            get_next_due(&Recurrence::MonthDay(1), None, today),
+            date(2020, 7, 1)
+        );
+        assert_eq!(
+            get_next_due(&Recurrence::MonthDay(20), None, today),
+            date(2020, 6, 20)
+        );
+        assert_eq!(
+            get_next_due(&Recurrence::MonthDay(31), Some(date(2020, 5, 31)), today),
+            date(2020, 6, 30)
+        );
+        assert_eq!(
+            get_next_due(&Recurrence::MonthDay(31), None, today),
+            date(2020, 6, 30)
+        );
+    }
+
+    #[test]
+    fn test_text_set_due() {
+        let date = NaiveDate::from_ymd_opt(2020, 6, 22).unwrap();
+        assert_eq!(
+            text_set_due("Chores due:2020-06-15 every:week", date),
+            "Chores due:2020-06-22 every:week"
+        );
+        assert_eq!(
+            text_set_due("Chores every:week", date),
+            "Chores every:week due:2020-06-22"
+        );
+    }
+
+    #[test]
+    fn test_text_set_done() {
+        assert_eq!(
+            text_set_done("Pay the bills", Some("2020-06-15")),
+            "Pay the bills done:2020-06-15"
+        );
+        assert_eq!(
+            text_set_done(
+                "Pay the bills done:2020-06-01 #home",
+                Some("2020-06-15T09:30")
+            ),
+            "Pay the bills #home done:2020-06-15T09:30"
+        );
+        assert_eq!(
+            text_set_done("Pay the bills done:2020-06-15T09:30 #home", None),
+            "Pay the bills #home"
+        );
+        assert_eq!(
+            text_get_done_date("Pay the bills done:2020-06-15T09:30"),
+            NaiveDate::from_ymd_opt(2020, 6, 15)
+        );
+    }
+
+    #[test]
+    fn test_text_focus_metadata() {
+        let name = "Some task created:2020-06-15 ^7iyc done:2020-06-16";
+        let focused_name = "**Some task** created:2020-06-15 ^7iyc done:2020-06-16";
+        assert_eq!(text_add_focus(name, FileFormat::Markdown), focused_name);
+        assert!(text_is_focused(focused_name, FileFormat::Markdown));
+        assert_eq!(text_remove_focus(focused_name, FileFormat::Markdown), name);
+
+        // the metadata may also be in the markup
+        assert!(text_is_focused("**Some task ^7iyc**", FileFormat::Markdown));
+        assert_eq!(
+            text_remove_focus("**Some task ^7iyc**", FileFormat::Markdown),
+            "Some task ^7iyc"
+        );
+
+        assert!(!text_is_focused("Some **bold** task", FileFormat::Markdown));
+
+        // the priority cookie and the tags of an Org headline stay out too
+        let focused_name = "[#A] *Release* :work:";
+        assert_eq!(
+            text_add_focus("[#A] Release :work:", FileFormat::Org),
+            focused_name
+        );
+        assert!(text_is_focused(focused_name, FileFormat::Org));
+        assert_eq!(
+            text_remove_focus(focused_name, FileFormat::Org),
+            "[#A] Release :work:"
+        );
+        assert_eq!(
+            text_add_focus("See https://example.com", FileFormat::Markdown),
+            "**See https://example.com**"
+        );
+    }
+
+    #[test]
+    fn test_text_format_age() {
+        let today = NaiveDate::from_ymd_opt(2020, 6, 15).unwrap();
+        let age = |y, m, d| text_format_age(NaiveDate::from_ymd_opt(y, m, d).unwrap(), today);
+
+        assert_eq!(age(2020, 6, 15), "0 days");
+        assert_eq!(age(2020, 6, 14), "1 day");
+        assert_eq!(age(2020, 5, 25), "3 weeks");
+        assert_eq!(age(2020, 1, 10), "5 months");
+        assert_eq!(age(2017, 6, 1), "3 years");
+        assert_eq!(
+            text_get_created_date("Fix the roof created:2020-01-10T08:00 #home"),
+            NaiveDate::from_ymd_opt(2020, 1, 10)
+        );
+    }
+
+    #[test]
+    fn test_task_ids() {
+        let content_getter = ContentGetterMock::new(Ok(String::from(
+            "- [ ] First ^ab12\n- [ ] **Second ^cd34** // comment\n- [ ] Third",
+        )));
+        let (tasks, _, _, _) = get_all_tasks(&content_getter).unwrap();
+
+        assert_eq!(tasks[0].id, Some(String::from("ab12")));
+        assert_eq!(tasks[1].id, Some(String::from("cd34")));
+        assert_eq!(tasks[2].id, None);
+
+        assert_eq!(get_task_num_by_ref(&tasks, "3"), Some(3));
+        assert_eq!(get_task_num_by_ref(&tasks, "^cd34"), Some(2));
+        assert_eq!(get_task_num_by_ref(&tasks, "ab12"), Some(1));
+        assert_eq!(get_task_num_by_ref(&tasks, "^zzzz"), None);
+
+        let id = get_new_task_id(&tasks, 42);
+        assert_eq!(id.len(), 4);
+        assert_eq!(id, get_new_task_id(&tasks, 42));
+        assert!(text_get_id(&format!("Fourth ^{}", id)).is_some());
+
+        // the first candidate of this seed is "3183"
+        assert!(get_new_task_id(&tasks, 60)
+            .chars()
+            .any(|c| c.is_ascii_alphabetic()));
+
+        let content_getter =
+            ContentGetterMock::new(Ok(String::from("- [ ] One\n- [ ] Two\n- [ ] Three ^0001")));
+        let (tasks, _, _, _) = get_all_tasks(&content_getter).unwrap();
+        assert_eq!(get_task_num_by_ref(&tasks, "0001"), Some(3));
+        assert_eq!(get_task_num_by_ref(&tasks, "^0001"), Some(3));
+        assert_eq!(get_task_num_by_ref(&tasks, "1"), Some(1));
+
+        assert_eq!(
+            text_set_done("First ^ab12", Some("2020-06-15")),
+            "First done:2020-06-15 ^ab12"
+        );
+    }
+
+    #[test]
+    fn test_text_set_id() {
+        assert_eq!(text_set_id("Write code ^ab12", "7iyc"), "Write code ^7iyc");
+        assert_eq!(text_set_id("Write code", "7iyc"), "Write code ^7iyc");
+    }
+
+    #[test]
+    fn test_blocked_tasks() {
+        let content_getter = ContentGetterMock::new(Ok(String::from(
+            "- [ ] Deploy after:^ab12 after:cd34\n- [ ] Write code ^ab12\n- [x] Review ^cd34\n- [ ] Release after:^cd34 after:^zzzz",
+        )));
+        let (tasks, _, _, _) = get_all_tasks(&content_getter).unwrap();
+
+        assert_eq!(tasks[0].blocked_by, vec!["ab12", "cd34"]);
+        assert!(tasks[0].is_blocked);
+        assert!(!tasks[1].is_blocked);
+        assert!(!tasks[3].is_blocked);
+
+        match get_current_task(&content_getter, today_getter_fixed(), None) {
+            Ok(Some((task, _))) => assert_eq!(task.num, 2),
+            _ => panic!("Expected a current task"),
+        }
+    }
+
+    #[test]
+    fn test_estimates() {
+        assert_eq!(text_get_estimate("Write code ~2h"), Some(120));
+        assert_eq!(text_get_estimate("~30m Write code"), Some(30));
+        assert_eq!(text_get_estimate("Write code ~1h15m #dev"), Some(75));
+        assert_eq!(text_get_estimate("Write code ~ later"), None);
+        assert_eq!(text_format_duration(75), "1h15m");
+        assert_eq!(text_format_duration(120), "2h");
+        assert_eq!(text_format_duration(5), "5m");
+    }
+
+    #[test]
+    fn test_time_intervals() {
+        let contents = "ab12 2020-06-15T09:00:00 2020-06-15T09:45:00\ncd34 2020-06-15T10:00:00\n";
+        let intervals =
+            get_time_intervals(&ContentGetterMock::new(Ok(String::from(contents)))).unwrap();
+
+        assert_eq!(intervals.len(), 2);
+        assert_eq!(get_interval_minutes(&intervals[0], now_getter_fixed()), 45);
+        assert_eq!(intervals[1].end, None);
+        assert_eq!(time_intervals_to_string(&intervals), contents);
+
+        assert!(
+            get_time_intervals(&ContentGetterMock::new(Ok(String::from("ab12 yesterday"))))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_text_parse_since() {
+        let today = today_getter_fixed(); // monday
+
+        assert_eq!(text_parse_since("monday", today), Ok(today));
+        assert_eq!(
+            text_parse_since("Friday", today),
+            Ok(NaiveDate::from_ymd_opt(2020, 6, 12).unwrap())
+        );
+        assert_eq!(
+            text_parse_since("yesterday", today),
+            Ok(NaiveDate::from_ymd_opt(2020, 6, 14).unwrap())
+        );
+        assert_eq!(
+            text_parse_since("2020-01-01", today),
+            Ok(NaiveDate::from_ymd_opt(2020, 1, 1).unwrap())
+        );
+        assert!(text_parse_since("soon", today).is_err());
+    }
+
+    #[test]
+    fn test_get_all_tasks_org() {
+        let content_getter = ContentGetterMock::new_org(Ok([
+            "#+TITLE: Tasks",
+            "* Job",
+            "** TODO [#A] Send the invoice :work:",
+            "   DEADLINE: <2020-06-20 Sat> SCHEDULED: <2020-06-15 Mon>",
+            "   Some notes",
+            "*** DONE Print it",
+            "** STARTED *Fix the build*",
+            "   - [ ] Bump the compiler",
+            "   - [X] Clear the cache",
+            "#+BEGIN_SRC sh",
+            "* TODO Not a task",
+            "#+END_SRC",
+            "* Perso",
+            "- [ ] Fix the roof",
+        ]
+        .join("\n")));
+
+        let (tasks, use_sections, sections, _) = get_all_tasks(&content_getter).unwrap();
+
+        assert!(use_sections);
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].plain_name, "Job");
+        assert_eq!(sections[0].line_num_end, 12);
+        assert_eq!(tasks.len(), 6);
+
+        assert_eq!(tasks[0].plain_name, "[#A] Send the invoice :work:");
+        assert_eq!(tasks[0].org_level, Some(2));
+        assert_eq!(tasks[0].priority, Some('A'));
+        assert_eq!(tasks[0].tags, vec!["work"]);
+        assert_eq!(tasks[0].due, NaiveDate::from_ymd_opt(2020, 6, 20));
+        assert_eq!(tasks[0].line_num_end, 5);
+        assert_eq!(tasks[0].children, vec![2]);
+
+        assert_eq!(tasks[1].state, TaskState::Done);
+        assert_eq!(tasks[1].parent, Some(1));
+
+        assert_eq!(tasks[2].state, TaskState::InProgress);
+        assert!(tasks[2].is_focused);
+        assert_eq!(tasks[2].plain_name, "Fix the build");
+        assert_eq!(tasks[2].children, vec![4, 5]);
+
+        assert_eq!(tasks[3].org_level, None);
+        assert_eq!(tasks[4].state, TaskState::Done);
+        assert_eq!(tasks[5].section.as_ref().unwrap().plain_name, "Perso");
+
+        for task in &tasks {
+            assert_eq!(task_to_line(task), task.line);
+        }
+
+        let mut task = tasks[2].clone();
+        task.state = TaskState::Done;
+        task.is_focused = false;
+        assert_eq!(task_to_line(&task), "** DONE Fix the build");
+
+        let mut task = tasks[3].clone();
+        task.state = TaskState::Done;
+        assert_eq!(task_to_line(&task), "   - [X] Bump the compiler");
+
+        let mut section = sections[1].as_ref().clone();
+        section.is_focused = true;
+        assert_eq!(section_to_line(&section), "* *Perso*");
+    }
+
+    #[test]
+    fn test_get_all_tasks_todotxt() {
+        let content_getter = ContentGetterMock::new_todotxt(Ok([
+            "# focus: +job",
+            "(A) 2020-06-01 Send the invoice +job @mail due:2020-06-20",
+            "x 2020-06-12 2020-06-01 Fix the roof +home",
+            "",
+            "**Call mom** ^ab12 state:started // on sunday",
+            "(B) Release +job after:^ab12",
+            "x Buy milk state:cancelled",
+        ]
+        .join("\n")));
+
+        let (tasks, use_sections, sections, focused_sections) =
+            get_all_tasks(&content_getter).unwrap();
+
+        assert!(use_sections);
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].plain_name, "job");
+        assert_eq!((sections[0].line_num, sections[0].line_num_end), (2, 6));
+        assert_eq!(focused_sections[0].plain_name, "job");
+        assert_eq!(tasks.len(), 5);
+
+        assert_eq!(
+            tasks[0].plain_name,
+            "(A) Send the invoice +job @mail due:2020-06-20"
+        );
+        assert_eq!(tasks[0].priority, Some('A'));
+        assert_eq!(tasks[0].created, NaiveDate::from_ymd_opt(2020, 6, 1));
+        assert_eq!(tasks[0].contexts, vec!["mail"]);
+        assert_eq!(tasks[0].due, NaiveDate::from_ymd_opt(2020, 6, 20));
+
+        assert_eq!(tasks[1].state, TaskState::Done);
+        assert_eq!(tasks[1].done_date, NaiveDate::from_ymd_opt(2020, 6, 12));
+        assert_eq!(tasks[1].section.as_ref().unwrap().plain_name, "home");
+
+        assert_eq!(tasks[2].state, TaskState::InProgress);
+        assert!(tasks[2].is_focused);
+        assert_eq!(tasks[2].plain_name, "Call mom ^ab12");
+        assert_eq!(tasks[2].comment, Some(String::from("on sunday")));
+        assert_eq!(tasks[2].section, None);
+
+        assert!(tasks[3].is_blocked);
+        assert_eq!(tasks[3].section.as_ref().unwrap().plain_name, "job");
+        assert_eq!(tasks[4].state, TaskState::Cancelled);
+
+        for task in &tasks {
+            assert_eq!(task_to_line(task), task.line);
+        }
+
+        let mut task = tasks[0].clone();
+        task.state = TaskState::Done;
+        task.done_date = NaiveDate::from_ymd_opt(2020, 6, 15);
+        task.is_focused = true;
+        assert_eq!(
+            task_to_line(&task),
+            "x 2020-06-15 (A) 2020-06-01 **Send the invoice** +job @mail due:2020-06-20"
+        );
+
+        assert_eq!(
+            text_set_todotxt_focus("# focus: +job\nFix the roof +home", &[String::from("home")]),
+            "# focus: +home\nFix the roof +home"
+        );
+        assert_eq!(
+            text_set_todotxt_focus("# focus: +job\nFix the roof +home", &[]),
+            "Fix the roof +home"
+        );
+    }
+
+    #[test]
+    fn test_get_all_tasks_subsections() {
+        let content_getter = ContentGetterMock::new(Ok([
+            "# Job",
+            "## Backend",
+            "- [ ] Fix the API",
+            "### Database",
+            "- [ ] Migrate",
+            "# Perso",
+            "## Backend",
+            "- [ ] Fix the server",
+        ]
+        .join("\n")));
+        let (tasks, _, sections, _) = get_all_tasks(&content_getter).unwrap();
+
+        assert_eq!(sections[2].depth, 2);
+        assert_eq!(section_path(&sections[2]), "Job/Backend/Database");
+        assert_eq!(section_path(&sections[4]), "Perso/Backend");
+
+        let job = search_section("job", &sections).unwrap();
+        assert_eq!(filter_tasks_in_section_only(&tasks, &job).len(), 0);
+        assert_eq!(filter_tasks_in_sections(&tasks, &[Rc::new(job)]).len(), 2);
+
+        let backend = search_section("Perso/Backend", &sections).unwrap();
+        assert_eq!(backend.num, 5);
+        assert_eq!(
+            filter_tasks_in_sections(&tasks, &[Rc::new(backend)])[0].plain_name,
+            "Fix the server"
+        );
+        assert_eq!(search_section("backend/data", &sections).unwrap().num, 3);
+    }
+
+    #[test]
+    fn test_get_state() {
+        assert_eq!(text_get_state(""), TaskState::Open);
+        assert_eq!(text_get_state("*"), TaskState::Open);
+        assert_eq!(text_get_state("x"), TaskState::Done);
+        assert_eq!(text_get_state("X"), TaskState::Done);
+        assert_eq!(text_get_state("-"), TaskState::Cancelled);
+        assert_eq!(text_get_state("/"), TaskState::InProgress);
+        assert_eq!(text_get_state(">"), TaskState::Deferred);
     }
 
     #[test]
@@ -433,6 +2319,113 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_get_all_tasks_nested() {
+        let contents = [
+            "- [ ] Parent",
+            "  - [ ] Child",
+            "    - [ ] Grandchild",
+            "  - [ ] Second child",
+            "- [ ] Sibling",
+        ]
+        .join("\n");
+
+        match get_all_tasks(&ContentGetterMock::new(Ok(contents))) {
+            Ok((tasks, _, _, _)) => {
+                let tree: Vec<(usize, Option<usize>, Vec<usize>)> = tasks
+                    .iter()
+                    .map(|t| (t.depth, t.parent, t.children.clone()))
+                    .collect();
+
+                assert_eq!(
+                    tree,
+                    vec![
+                        (0, None, vec![2, 4]),
+                        (1, Some(1), vec![3]),
+                        (2, Some(2), vec![]),
+                        (1, Some(1), vec![]),
+                        (0, None, vec![]),
+                    ]
+                );
+                assert_eq!(task_to_line(&tasks[2]), "    - [ ] Grandchild");
+            }
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn test_get_all_tasks_notes_after_subtask() {
+        let contents = [
+            "- [ ] Parent",
+            "  - [ ] Child",
+            "    Notes of child",
+            "  Notes of parent",
+            "Not a note",
+        ]
+        .join("\n");
+
+        match get_all_tasks(&ContentGetterMock::new(Ok(contents))) {
+            Ok((tasks, _, _, _)) => {
+                assert_eq!(tasks[0].body, vec!["  Notes of parent"]);
+                assert_eq!(tasks[0].line_num_end, 4);
+                assert_eq!(tasks[1].body, vec!["    Notes of child"]);
+                assert_eq!(get_task_line_nums(&tasks, &tasks[0]), vec![1, 2, 3, 4]);
+            }
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn test_get_all_tasks_markdown_blocks() {
+        let contents = [
+            "---",
+            "# not a section: front matter",
+            "---",
+            "",
+            "Job",
+            "===",
+            "",
+            "- [ ] Real task",
+            "",
+            "```bash",
+            "# not a section: code",
+            "- [ ] not a task: code sample",
+            "```",
+            "",
+            "<!--",
+            "- [ ] not a task: html comment",
+            "-->",
+            "",
+            "> # not a section: block quote",
+            "",
+            "## Perso",
+            "",
+            "- [ ] Other task",
+        ]
+        .join("\n");
+
+        match get_all_tasks(&ContentGetterMock::new(Ok(contents))) {
+            Ok((tasks, use_sections, sections, _)) => {
+                assert!(use_sections);
+                assert_eq!(
+                    sections
+                        .iter()
+                        .map(|s| (s.name.as_str(), s.level, s.line_num, s.line_num_end))
+                        .collect::<Vec<_>>(),
+                    vec![("Job", 1, 5, 20), ("Perso", 2, 21, 23)]
+                );
+                assert_eq!(
+                    tasks
+                        .iter()
+                        .map(|t| (t.name.as_str(), t.line_num, t.section.as_ref().unwrap().num))
+                        .collect::<Vec<_>>(),
+                    vec![("Real task", 8, 1), ("Other task", 23, 2)]
+                );
+            }
+            Err(e) => panic!("{}", e),
+        }
+    }
+
     #[test]
     fn test_get_open_tasks() {
         // Empty contents
@@ -461,7 +2454,11 @@ mod tests {
     #[test]
     fn test_get_current_task() {
         // Empty contents
-        match get_current_task(&ContentGetterMock::new(Ok("".to_string())), false) {
+        match get_current_task(
+            &ContentGetterMock::new(Ok("".to_string())),
+            today_getter_fixed(),
+            None,
+        ) {
             Ok(task) => assert_eq!(task, None),
             Err(e) => panic!("{}", e),
         }
@@ -469,10 +2466,136 @@ mod tests {
         // Std contents
         let (test_contents, expected_tasks) = get_std_test_contents();
 
-        match get_current_task(&ContentGetterMock::new(Ok(test_contents)), false) {
+        match get_current_task(
+            &ContentGetterMock::new(Ok(test_contents)),
+            today_getter_fixed(),
+            None,
+        ) {
             Ok(task) => assert_eq!(task, Some((expected_tasks[1].clone(), false))),
             Err(e) => panic!("{}", e),
         }
+
+        // Due dates
+        let due_contents = [
+            "- [ ] No due date",
+            "- [ ] Later due:2020-07-01",
+            "- [ ] Overdue due:2020-06-01",
+            "- [ ] Due today due:2020-06-15",
+        ]
+        .join("\n");
+
+        match get_current_task(
+            &ContentGetterMock::new(Ok(due_contents)),
+            today_getter_fixed(),
+            None,
+        ) {
+            Ok(Some((task, _))) => assert_eq!(task.plain_name, "Overdue due:2020-06-01"),
+            Ok(None) => panic!("No current task"),
+            Err(e) => panic!("{}", e),
+        }
+
+        // Priorities
+        let priority_contents = [
+            "- [ ] No priority",
+            "- [ ] (C) Low priority",
+            "- [ ] High priority !!!",
+            "- [ ] (A) Other high priority",
+        ]
+        .join("\n");
+
+        match get_current_task(
+            &ContentGetterMock::new(Ok(priority_contents)),
+            today_getter_fixed(),
+            None,
+        ) {
+            Ok(Some((task, _))) => assert_eq!(task.plain_name, "High priority !!!"),
+            Ok(None) => panic!("No current task"),
+            Err(e) => panic!("{}", e),
+        }
+
+        // States
+        let state_contents = [
+            "- [>] Deferred !!!",
+            "- [ ] Open !!",
+            "- [/] In progress",
+            "- [-] Cancelled",
+        ]
+        .join("\n");
+
+        match get_current_task(
+            &ContentGetterMock::new(Ok(state_contents)),
+            today_getter_fixed(),
+            None,
+        ) {
+            Ok(Some((task, _))) => assert_eq!(task.plain_name, "In progress"),
+            Ok(None) => panic!("No current task"),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn test_get_all_tasks_front_matter() {
+        let front_matter = "---\nsection: Inbox\n# - [ ] Not a task\n---\n";
+        assert_eq!(
+            text_get_front_matter(front_matter),
+            Some((vec!["section: Inbox", "# - [ ] Not a task"], 4))
+        );
+        assert_eq!(text_get_front_matter("# Job\n---\n"), None);
+
+        let (tasks, _, sections, _) = get_all_tasks(&ContentGetterMock::new(Ok(format!(
+            "{}# Job\n- [ ] Send the invoice\n",
+            front_matter
+        ))))
+        .unwrap();
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].plain_name, "Job");
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].line_num, 6);
+
+        let (tasks, _, sections, _) = get_all_tasks(&ContentGetterMock::new_todotxt(Ok(format!(
+            "{}Send the invoice +job\n",
+            front_matter
+        ))))
+        .unwrap();
+        assert_eq!(sections.len(), 1);
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].line_num, 5);
+    }
+
+    #[test]
+    fn test_get_all_tasks_aggregated() {
+        let content = [
+            "<!-- tax file: work.md -->",
+            "# Backend",
+            "- [ ] Fix the API",
+            "```",
+            "<!-- tax file: home.md -->",
+            "---",
+            "section: Perso",
+            "---",
+            "- [ ] Fix the roof",
+            "# Perso",
+            "- [ ] Call mom",
+        ]
+        .join("\n");
+
+        let (tasks, use_sections, sections, _) =
+            get_all_tasks(&ContentGetterMock::new(Ok(content))).unwrap();
+
+        assert!(use_sections);
+        assert_eq!(sections.len(), 4);
+        assert_eq!(sections[0].plain_name, "work");
+        assert_eq!((sections[0].level, sections[0].line_num_end), (0, 1));
+        assert_eq!(section_path(&sections[1]), "work/Backend");
+        assert_eq!(sections[2].plain_name, "home");
+        assert_eq!(section_path(&sections[3]), "home/Perso");
+        assert_eq!(section_to_line(&sections[2]), "<!-- tax file: home.md -->");
+
+        // the unclosed code block ends with its file
+        assert_eq!(tasks.len(), 3);
+        assert_eq!(tasks[1].plain_name, "Fix the roof");
+        assert_eq!(tasks[1].line_num, 9);
+        assert_eq!(tasks[1].section.as_ref().unwrap().plain_name, "home");
     }
 
     #[test]
@@ -482,15 +2605,46 @@ mod tests {
         assert_eq!(s, "Hello, World!\n");
 
         let s = text_add_line_in_str("first line", 1, "Hello, World!");
-        assert_eq!(s, "Hello, World!\nfirst line\n");
+        assert_eq!(s, "Hello, World!\nfirst line");
 
         let s = text_add_line_in_str("first line", 2, "Hello, World!");
-        assert_eq!(s, "first line\nHello, World!\n");
+        assert_eq!(s, "first line\nHello, World!");
 
         let s = text_add_line_in_str("first line\nlast line", 2, "Hello, World!");
-        assert_eq!(s, "first line\nHello, World!\nlast line\n");
+        assert_eq!(s, "first line\nHello, World!\nlast line");
 
         let s = text_add_line_in_str("# Header\n\n- [ ] Do the stuff", 3, "Hello, World!");
-        assert_eq!(s, "# Header\n\nHello, World!\n- [ ] Do the stuff\n");
+        assert_eq!(s, "# Header\n\nHello, World!\n- [ ] Do the stuff");
+    }
+
+    #[test]
+    fn test_line_endings_preserved() {
+        let crlf = "\u{feff}# Header\r\n- [ ] First\r\n- [ ] Last\r\n";
+
+        let s = text_add_line_in_str(crlf, 2, "- [ ] Added");
+        assert_eq!(
+            s,
+            "\u{feff}# Header\r\n- [ ] Added\r\n- [ ] First\r\n- [ ] Last\r\n"
+        );
+
+        let s = text_add_line_in_str(crlf, 4, "\n- [ ] Added");
+        assert_eq!(
+            s,
+            "\u{feff}# Header\r\n- [ ] First\r\n- [ ] Last\r\n\r\n- [ ] Added\r\n"
+        );
+
+        let s = text_replace_line_in_str(crlf, 1, "# Focused");
+        assert_eq!(s, "\u{feff}# Focused\r\n- [ ] First\r\n- [ ] Last\r\n");
+
+        let s = text_remove_lines_in_str(crlf, vec![3]).unwrap();
+        assert_eq!(s, "\u{feff}# Header\r\n- [ ] First\r\n");
+
+        let s = text_remove_lines_in_str("- [ ] First\n- [ ] Last", vec![2]).unwrap();
+        assert_eq!(s, "- [ ] First");
+
+        let content_getter = ContentGetterMock::new(Ok(crlf.to_string()));
+        let (tasks, _, sections, _) = get_all_tasks(&content_getter).unwrap();
+        assert_eq!(sections[0].plain_name, "Header");
+        assert_eq!(tasks[0].line, "- [ ] First");
     }
 }
@@ -13,6 +13,20 @@ pub mod test {
         Some(std::path::PathBuf::from("/home/guybrush"))
     }
 
+    pub fn config_dir_getter_guybrush() -> Option<std::path::PathBuf> {
+        Some(std::path::PathBuf::from("/home/guybrush/.config"))
+    }
+
+    #[allow(dead_code)]
+    pub fn today_getter_fixed() -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd_opt(2020, 6, 15).unwrap()
+    }
+
+    #[allow(dead_code)]
+    pub fn now_getter_fixed() -> chrono::NaiveDateTime {
+        today_getter_fixed().and_hms_opt(9, 30, 0).unwrap()
+    }
+
     #[allow(dead_code)]
     pub fn env_getter_taxfile(name: &str) -> Option<String> {
         match name {
@@ -25,12 +39,32 @@ pub mod test {
     // ////////////////////////////////////////////////////////////////////////////
     pub struct ContentGetterMock {
         outcome: Result<String, String>,
+        format: crate::model::FileFormat,
     }
 
     impl ContentGetterMock {
         #[allow(dead_code)]
         pub fn new(outcome: Result<String, String>) -> Self {
-            ContentGetterMock { outcome }
+            ContentGetterMock {
+                outcome,
+                format: crate::model::FileFormat::Markdown,
+            }
+        }
+
+        #[allow(dead_code)]
+        pub fn new_org(outcome: Result<String, String>) -> Self {
+            ContentGetterMock {
+                outcome,
+                format: crate::model::FileFormat::Org,
+            }
+        }
+
+        #[allow(dead_code)]
+        pub fn new_todotxt(outcome: Result<String, String>) -> Self {
+            ContentGetterMock {
+                outcome,
+                format: crate::model::FileFormat::TodoTxt,
+            }
         }
     }
 
@@ -38,6 +72,10 @@ pub mod test {
         fn get_contents(&self) -> Result<String, String> {
             self.outcome.clone()
         }
+
+        fn get_format(&self) -> crate::model::FileFormat {
+            self.format
+        }
     }
 
     // ////////////////////////////////////////////////////////////////////////////
@@ -116,7 +154,7 @@ pub mod test {
     #[allow(dead_code)]
     pub struct TaskFormatterMock {}
 
-    #[allow(dead_code)]
+    #[allow(dead_code, clippy::useless_vec)]
     pub fn get_std_test_contents() -> (String, Vec<crate::model::Task>) {
         (
             vec![
@@ -134,73 +172,85 @@ pub mod test {
                 crate::model::Task {
                     num: 1,
                     line_num: 2,
+                    line_num_end: 2,
                     line: String::from("- [ ] Standard unchecked"),
                     name: String::from("Standard unchecked"),
                     plain_name: String::from("Standard unchecked"),
-                    is_checked: false,
+                    state: crate::model::TaskState::Open,
                     is_focused: false,
                     comment: None,
                     section: None,
+                    ..Default::default()
                 },
                 crate::model::Task {
                     num: 2,
                     line_num: 3,
+                    line_num_end: 3,
                     line: String::from("- [ ] **Standard unchecked focused**"),
                     name: String::from("**Standard unchecked focused**"),
                     plain_name: String::from("Standard unchecked focused"),
-                    is_checked: false,
+                    state: crate::model::TaskState::Open,
                     is_focused: true,
                     comment: None,
                     section: None,
+                    ..Default::default()
                 },
                 crate::model::Task {
                     num: 3,
                     line_num: 5,
+                    line_num_end: 5,
                     line: String::from("- [x] Checked"),
                     name: String::from("Checked"),
                     plain_name: String::from("Checked"),
-                    is_checked: true,
+                    state: crate::model::TaskState::Done,
                     is_focused: false,
                     comment: None,
                     section: None,
+                    ..Default::default()
                 },
                 crate::model::Task {
                     num: 4,
                     line_num: 6,
+                    line_num_end: 6,
                     line: String::from("- [x] **Focused checked**"),
                     name: String::from("**Focused checked**"),
                     plain_name: String::from("Focused checked"),
-                    is_checked: true,
+                    state: crate::model::TaskState::Done,
                     is_focused: true,
                     comment: None,
                     section: None,
+                    ..Default::default()
                 },
                 crate::model::Task {
                     num: 5,
                     line_num: 7,
+                    line_num_end: 7,
                     line: String::from("- [ ] Standard unchecked // with comments"),
                     name: String::from("Standard unchecked"),
                     plain_name: String::from("Standard unchecked"),
-                    is_checked: false,
+                    state: crate::model::TaskState::Open,
                     is_focused: false,
                     comment: Some(String::from("with comments")),
                     section: None,
+                    ..Default::default()
                 },
                 crate::model::Task {
                     num: 6,
                     line_num: 8,
+                    line_num_end: 8,
                     line: String::from("- [ ] **Standard unchecked focused** // with comments"),
                     name: String::from("**Standard unchecked focused**"),
                     plain_name: String::from("Standard unchecked focused"),
-                    is_checked: false,
+                    state: crate::model::TaskState::Open,
                     is_focused: true,
                     comment: Some(String::from("with comments")),
                     section: None,
+                    ..Default::default()
                 },
             ],
         )
     }
-    #[allow(dead_code)]
+    #[allow(dead_code, clippy::useless_vec)]
     pub fn get_std_test_tasks() -> (String, Vec<crate::model::Task>) {
         (
             vec![
@@ -216,68 +266,80 @@ pub mod test {
                 crate::model::Task {
                     num: 1,
                     line_num: 1,
+                    line_num_end: 1,
                     line: String::from("- [ ] Standard unchecked"),
                     name: String::from("Standard unchecked"),
                     plain_name: String::from("Standard unchecked"),
-                    is_checked: false,
+                    state: crate::model::TaskState::Open,
                     is_focused: false,
                     comment: None,
                     section: None,
+                    ..Default::default()
                 },
                 crate::model::Task {
                     num: 2,
                     line_num: 2,
+                    line_num_end: 2,
                     line: String::from("- [ ] **Standard unchecked focused**"),
                     name: String::from("**Standard unchecked focused**"),
                     plain_name: String::from("Standard unchecked focused"),
-                    is_checked: false,
+                    state: crate::model::TaskState::Open,
                     is_focused: true,
                     comment: None,
                     section: None,
+                    ..Default::default()
                 },
                 crate::model::Task {
                     num: 3,
                     line_num: 3,
+                    line_num_end: 3,
                     line: String::from("- [x] Checked"),
                     name: String::from("Checked"),
                     plain_name: String::from("Checked"),
-                    is_checked: true,
+                    state: crate::model::TaskState::Done,
                     is_focused: false,
                     comment: None,
                     section: None,
+                    ..Default::default()
                 },
                 crate::model::Task {
                     num: 4,
                     line_num: 4,
+                    line_num_end: 4,
                     line: String::from("- [x] **Focused checked**"),
                     name: String::from("**Focused checked**"),
                     plain_name: String::from("Focused checked"),
-                    is_checked: true,
+                    state: crate::model::TaskState::Done,
                     is_focused: true,
                     comment: None,
                     section: None,
+                    ..Default::default()
                 },
                 crate::model::Task {
                     num: 5,
                     line_num: 5,
+                    line_num_end: 5,
                     line: String::from("- [ ] Standard unchecked // with comments"),
                     name: String::from("Standard unchecked"),
                     plain_name: String::from("Standard unchecked"),
-                    is_checked: false,
+                    state: crate::model::TaskState::Open,
                     is_focused: false,
                     comment: Some(String::from("with comments")),
                     section: None,
+                    ..Default::default()
                 },
                 crate::model::Task {
                     num: 6,
                     line_num: 6,
+                    line_num_end: 6,
                     line: String::from("- [ ] **Standard unchecked focused** // with comments"),
                     name: String::from("**Standard unchecked focused**"),
                     plain_name: String::from("Standard unchecked focused"),
-                    is_checked: false,
+                    state: crate::model::TaskState::Open,
                     is_focused: true,
                     comment: Some(String::from("with comments")),
                     section: None,
+                    ..Default::default()
                 },
             ],
         )